@@ -0,0 +1,57 @@
+use crate::{
+    environment::EnvironmentMap,
+    vec3::{Color, Vec3},
+};
+use rand::Rng;
+
+/// Light reaching the camera from rays which do not hit any object in the scene.
+pub enum Background {
+    /// No light at all. Useful for scenes lit exclusively by emitting objects.
+    Black,
+    /// The same color in every direction.
+    Solid(Color),
+    /// Blends linearly from `bottom` (looking straight down) to `top` (looking straight up).
+    Gradient { bottom: Color, top: Color },
+    /// Image surrounding the scene.
+    Environment(EnvironmentMap),
+}
+
+impl Background {
+    pub fn color(&self, direction: &Vec3) -> Color {
+        match self {
+            Background::Black => Color::ZERO,
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                // y is between -1 and 1
+                let y = direction.unit().y();
+                // 0 <= t <= 1
+                let t = (y + 1.) / 2.;
+                *bottom * (1. - t) + *top * t
+            }
+            Background::Environment(map) => map.color(direction),
+        }
+    }
+
+    /// `true` if the background is bright in some directions, and dark in others, so it pays off
+    /// to sample it explicitly.
+    pub fn is_importance_sampled(&self) -> bool {
+        matches!(self, Background::Environment(_))
+    }
+
+    /// Random direction, chosen proportional to the light the background emits in it. Only to be
+    /// called if `is_importance_sampled` is `true`.
+    pub fn sample_direction(&self, rng: &mut (impl Rng + ?Sized)) -> Vec3 {
+        match self {
+            Background::Environment(map) => map.sample_direction(rng),
+            _ => panic!("Background does not support importance sampling."),
+        }
+    }
+
+    /// Probability density of `sample_direction` returning `direction`.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        match self {
+            Background::Environment(map) => map.pdf(direction),
+            _ => 0.,
+        }
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     ray::Ray,
     vec3::{Point, Vec3},
 };
-use std::{cmp::Ordering, mem::swap};
+use std::{cmp::Ordering, mem::swap, sync::Arc};
 
 pub trait BoundingBox {
     fn bounding_box(&self, exposure_time: f64) -> Aabb;
@@ -25,6 +25,23 @@ impl Aabb {
         Self { min, max }
     }
 
+    pub fn min(&self) -> Point {
+        self.min
+    }
+
+    pub fn max(&self) -> Point {
+        self.max
+    }
+
+    pub fn centroid(&self) -> Point {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.max - self.min;
+        2. * (extent.x() * extent.y() + extent.y() * extent.z() + extent.z() * extent.x())
+    }
+
     pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
         Self {
             min: Point::new(
@@ -71,7 +88,16 @@ impl Aabb {
 
 impl<B> BoundingBox for Box<B>
 where
-    B: BoundingBox,
+    B: BoundingBox + ?Sized,
+{
+    fn bounding_box(&self, exposure_time: f64) -> Aabb {
+        self.as_ref().bounding_box(exposure_time)
+    }
+}
+
+impl<B> BoundingBox for Arc<B>
+where
+    B: BoundingBox + ?Sized,
 {
     fn bounding_box(&self, exposure_time: f64) -> Aabb {
         self.as_ref().bounding_box(exposure_time)
@@ -1,122 +1,515 @@
 use crate::{
     bounding_box::{Aabb, BoundingBox},
     hittable::{Hit, Hittable},
+    ray::Ray,
     renderable::Renderable,
+    vec3::Point,
 };
-use std::cmp::Ordering;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, str::FromStr};
 
 pub trait BoundedHittable: Hittable + BoundingBox + Send + Sync {}
 impl<T> BoundedHittable for T where T: Hittable + BoundingBox + Send + Sync {}
 
+type HittableList = Vec<Box<dyn BoundedHittable>>;
+
+/// How the hittables are divided between the two children of each node in the hierarchy.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum BvhStrategy {
+    /// Binned surface area heuristic. Chooses axis and position of each split by minimizing the
+    /// expected cost of tracing a ray through the children.
+    #[default]
+    Sah,
+    /// Sorts along an axis, which changes with the size of the list, and assigns the hittables to
+    /// the children alternately. Fast to build, but produces largely overlapping children.
+    Alternate,
+}
+
+impl FromStr for BvhStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sah" => Ok(BvhStrategy::Sah),
+            "alternate" => Ok(BvhStrategy::Alternate),
+            _ => Err(format!(
+                "Unknown BVH strategy '{}'. Expected one of: sah, alternate.",
+                s
+            )),
+        }
+    }
+}
+
 pub fn into_bounding_volume_hierarchy(
     mut hittables: Vec<Box<dyn BoundedHittable>>,
     exposure_time: f64,
+    strategy: BvhStrategy,
 ) -> Box<dyn Renderable + Send + Sync> {
     match hittables.len() {
         0 => Box::new(hittables),
         1 => Box::new(hittables.drain(..).next().unwrap()),
-        _ => {
-            let (left, right) = split_list(hittables, exposure_time);
-            let left = into_bvh_impl(left, exposure_time);
-            let right = into_bvh_impl(right, exposure_time);
-            // Note that the top level note is stored as a dyn Hittable trait object, not
-            // BoundedHittable.
-            Box::new(BvhNode::new(left, right, exposure_time))
-        }
+        // Note that the top level note is stored as a dyn Hittable trait object, not
+        // BoundedHittable.
+        _ => Box::new(Bvh::new(hittables, exposure_time, strategy)),
     }
 }
 
-fn into_bvh_impl(
+/// Like [`into_bounding_volume_hierarchy`], but the result can itself be part of another
+/// hierarchy. Panics if `hittables` is empty.
+pub fn into_bounded_hierarchy(
     mut hittables: Vec<Box<dyn BoundedHittable>>,
     exposure_time: f64,
+    strategy: BvhStrategy,
 ) -> Box<dyn BoundedHittable> {
     match hittables.len() {
         0 => panic!("Can't construct hierarchie from empty scene."),
         1 => hittables.drain(..).next().unwrap(),
-        _ => {
-            let (even, odd) = split_list(hittables, exposure_time);
-            let left = into_bvh_impl(even, exposure_time);
-            let right = into_bvh_impl(odd, exposure_time);
-            Box::new(BvhNode::new(left, right, exposure_time))
-        }
+        _ => Box::new(Bvh::new(hittables, exposure_time, strategy)),
     }
 }
 
+/// Divides at least two hittables into two non empty lists. Also returns the axis along which they
+/// have been divided. If possible the first list holds the hittables with smaller coordinates.
 fn split_list(
+    hittables: Vec<Box<dyn BoundedHittable>>,
+    exposure_time: f64,
+    strategy: BvhStrategy,
+) -> (usize, HittableList, HittableList) {
+    match strategy {
+        BvhStrategy::Sah => split_sah(hittables, exposure_time),
+        BvhStrategy::Alternate => split_alternate(hittables, exposure_time),
+    }
+}
+
+fn split_alternate(
     mut hittables: Vec<Box<dyn BoundedHittable>>,
     exposure_time: f64,
-) -> (Vec<Box<dyn BoundedHittable>>, Vec<Box<dyn BoundedHittable>>) {
+) -> (usize, HittableList, HittableList) {
     let npot = hittables.len().next_power_of_two();
     let axis = npot.trailing_zeros() % 3;
 
-    hittables.sort_by(|l, r| sort_by_bounding_box(axis as usize, exposure_time, l, r));
+    hittables
+        .sort_by(|l, r| sort_by_bounding_box(axis as usize, exposure_time, l.as_ref(), r.as_ref()));
 
     let mut count = 0;
-    hittables.drain(..).partition(|_| {
+    let (even, odd) = hittables.drain(..).partition(|_| {
         count += 1;
         count % 2 == 0
-    })
+    });
+    (axis as usize, even, odd)
+}
+
+/// Number of buckets the centroids are sorted into along each axis. Splits are only considered
+/// between buckets.
+const SAH_BINS: usize = 12;
+
+fn split_sah(
+    hittables: Vec<Box<dyn BoundedHittable>>,
+    exposure_time: f64,
+) -> (usize, HittableList, HittableList) {
+    let boxes: Vec<_> = hittables
+        .iter()
+        .map(|hittable| hittable.bounding_box(exposure_time))
+        .collect();
+    let centroids = boxes
+        .iter()
+        .map(|aabb| {
+            let centroid = aabb.centroid();
+            Aabb::new(centroid, centroid)
+        })
+        .reduce(|a, b| Aabb::surrounding(&a, &b))
+        .unwrap();
+    let bin_of = |aabb: &Aabb, axis: usize| {
+        let (min, max) = (centroids.min()[axis], centroids.max()[axis]);
+        let relative = (aabb.centroid()[axis] - min) / (max - min);
+        ((relative * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+    };
+
+    // Axis and first bin of the right child for the cheapest split found so far.
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        if centroids.max()[axis] <= centroids.min()[axis] {
+            continue;
+        }
+        let mut bins: [(usize, Option<Aabb>); SAH_BINS] = [(0, None); SAH_BINS];
+        for aabb in &boxes {
+            let (count, bounds) = &mut bins[bin_of(aabb, axis)];
+            *count += 1;
+            *bounds = Some(bounds.map_or(*aabb, |bounds| Aabb::surrounding(&bounds, aabb)));
+        }
+        for split in 1..SAH_BINS {
+            let (left_count, left_area) = summarize(&bins[..split]);
+            let (right_count, right_area) = summarize(&bins[split..]);
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            // The probability of a ray hitting a child is proportional to its surface area. We
+            // can omit dividing by the area of the parent, as it is the same for all splits.
+            let cost = left_area * left_count as f64 + right_area * right_count as f64;
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    match best {
+        Some((_, axis, split)) => {
+            let (left, right): (Vec<_>, Vec<_>) = hittables
+                .into_iter()
+                .zip(&boxes)
+                .partition(|(_, aabb)| bin_of(aabb, axis) < split);
+            (
+                axis,
+                left.into_iter().map(|(hittable, _)| hittable).collect(),
+                right.into_iter().map(|(hittable, _)| hittable).collect(),
+            )
+        }
+        // All centroids are at the same position, so no split is better than another.
+        None => split_in_half(hittables),
+    }
+}
+
+fn split_in_half(mut hittables: HittableList) -> (usize, HittableList, HittableList) {
+    let right = hittables.split_off(hittables.len() / 2);
+    (0, hittables, right)
+}
+
+/// Number of hittables in `bins` and the surface area of their bounding box.
+fn summarize(bins: &[(usize, Option<Aabb>)]) -> (usize, f64) {
+    let count = bins.iter().map(|(count, _)| count).sum();
+    let bounds = bins
+        .iter()
+        .filter_map(|(_, bounds)| *bounds)
+        .reduce(|a, b| Aabb::surrounding(&a, &b));
+    (count, bounds.map_or(0., |bounds| bounds.surface_area()))
 }
 
 fn sort_by_bounding_box(
     axis: usize,
     exposure_time: f64,
-    a: &Box<dyn BoundedHittable>,
-    b: &Box<dyn BoundedHittable>,
+    a: &dyn BoundedHittable,
+    b: &dyn BoundedHittable,
 ) -> Ordering {
     let a = a.bounding_box(exposure_time);
     let b = b.bounding_box(exposure_time);
     a.cmp_min_axis(axis, &b)
 }
 
-struct BvhNode {
+/// Nodes deeper than this are split in half, rather than using the strategy of the hierarchy.
+/// Limits the depth of the tree for degenerated inputs, which keeps the traversal stack small.
+const MAX_STRATEGY_DEPTH: usize = 40;
+
+/// Hierarchies up to this depth are traversed with a stack allocated on the call stack. Splitting
+/// in half below [`MAX_STRATEGY_DEPTH`] keeps hierarchies of up to 16 million hittables within
+/// this limit. Deeper ones allocate their traversal stack on the heap.
+const TRAVERSAL_STACK_SIZE: usize = 64;
+
+/// Nodes with at least this many hittables build their two subtrees in parallel. Below, the
+/// overhead of spawning tasks and merging the subtrees outweighs the gain.
+const PARALLEL_BUILD_THRESHOLD: usize = 4096;
+
+/// Bounding volume hierarchy stored as a flat array of nodes in depth first order. The first child
+/// of an interior node directly follows its parent, so only the index of the second child needs
+/// to be stored.
+struct Bvh {
+    nodes: Vec<LinearNode>,
+    hittables: Vec<Box<dyn BoundedHittable>>,
+    /// Largest number of interior nodes on a path from the root to a leaf. The traversal stack
+    /// never holds more entries than this.
+    depth: usize,
+}
+
+struct LinearNode {
     bounding_box: Aabb,
-    left: Box<dyn BoundedHittable>,
-    right: Box<dyn BoundedHittable>,
+    kind: NodeKind,
 }
 
-impl BvhNode {
-    fn new(
-        left: Box<dyn BoundedHittable>,
-        right: Box<dyn BoundedHittable>,
+enum NodeKind {
+    Leaf {
+        /// Index into the hittables of the hierarchy.
+        hittable: usize,
+    },
+    Interior {
+        second_child: usize,
+        /// Axis along which the children have been split. The first child holds the hittables with
+        /// smaller coordinates.
+        axis: usize,
+    },
+}
+
+impl Bvh {
+    fn new(hittables: HittableList, exposure_time: f64, strategy: BvhStrategy) -> Self {
+        Self::subtree(hittables, exposure_time, strategy, 0)
+    }
+
+    /// Hierarchy for `hittables`, which may later be appended to a larger one. `depth` is the
+    /// depth its root is going to have within the complete hierarchy.
+    fn subtree(
+        hittables: HittableList,
         exposure_time: f64,
+        strategy: BvhStrategy,
+        depth: usize,
     ) -> Self {
-        let bounding_box = Aabb::surrounding(
-            &left.bounding_box(exposure_time),
-            &right.bounding_box(exposure_time),
-        );
-        Self {
-            left,
-            right,
-            bounding_box,
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * hittables.len() - 1),
+            hittables: Vec::with_capacity(hittables.len()),
+            depth: 0,
+        };
+        bvh.build(hittables, exposure_time, strategy, depth);
+        bvh
+    }
+
+    /// Appends the subtree containing `hittables` and returns its bounding box.
+    fn build(
+        &mut self,
+        mut hittables: HittableList,
+        exposure_time: f64,
+        strategy: BvhStrategy,
+        depth: usize,
+    ) -> Aabb {
+        let index = self.nodes.len();
+        if hittables.len() == 1 {
+            let hittable = hittables.pop().unwrap();
+            let bounding_box = hittable.bounding_box(exposure_time);
+            self.nodes.push(LinearNode {
+                bounding_box,
+                kind: NodeKind::Leaf {
+                    hittable: self.hittables.len(),
+                },
+            });
+            self.hittables.push(hittable);
+            return bounding_box;
         }
+        let (axis, first, second) = if depth < MAX_STRATEGY_DEPTH {
+            split_list(hittables, exposure_time, strategy)
+        } else {
+            split_in_half(hittables)
+        };
+        // Placeholder, until we know the index of the second child and the bounding box.
+        self.nodes.push(LinearNode {
+            bounding_box: Aabb::new(Point::new(0., 0., 0.), Point::new(0., 0., 0.)),
+            kind: NodeKind::Leaf { hittable: 0 },
+        });
+        let (first_box, second_child, second_box) =
+            if first.len() + second.len() >= PARALLEL_BUILD_THRESHOLD {
+                let (first, second) = rayon::join(
+                    || Self::subtree(first, exposure_time, strategy, depth + 1),
+                    || Self::subtree(second, exposure_time, strategy, depth + 1),
+                );
+                let first_box = self.append(first);
+                let second_child = self.nodes.len();
+                (first_box, second_child, self.append(second))
+            } else {
+                let first_box = self.build(first, exposure_time, strategy, depth + 1);
+                let second_child = self.nodes.len();
+                let second_box = self.build(second, exposure_time, strategy, depth + 1);
+                (first_box, second_child, second_box)
+            };
+        let bounding_box = Aabb::surrounding(&first_box, &second_box);
+        self.depth = self.depth.max(depth + 1);
+        self.nodes[index] = LinearNode {
+            bounding_box,
+            kind: NodeKind::Interior { second_child, axis },
+        };
+        bounding_box
+    }
+
+    /// Appends the nodes and hittables of `subtree` and returns its bounding box. The result is the
+    /// same as if the subtree had been built in place.
+    fn append(&mut self, subtree: Bvh) -> Aabb {
+        let node_offset = self.nodes.len();
+        let hittable_offset = self.hittables.len();
+        let bounding_box = subtree.nodes[0].bounding_box;
+        self.nodes.extend(subtree.nodes.into_iter().map(|mut node| {
+            match &mut node.kind {
+                NodeKind::Leaf { hittable } => *hittable += hittable_offset,
+                NodeKind::Interior { second_child, .. } => *second_child += node_offset,
+            }
+            node
+        }));
+        self.hittables.extend(subtree.hittables);
+        // The subtree has been built knowing its depth within this hierarchy.
+        self.depth = self.depth.max(subtree.depth);
+        bounding_box
     }
 }
 
-impl Hittable for BvhNode {
-    fn hit(&self, ray: &crate::ray::Ray, t_min: f64, t_max: f64, time: f64) -> Option<(f64, Hit)> {
-        if self.bounding_box.hit(ray, t_min, t_max) {
-            let l = self.left.hit(ray, t_min, t_max, time);
-            let r = self.right.hit(ray, t_min, t_max, time);
-            match (l, r) {
-                (None, None) => None,
-                (Some(hr), None) | (None, Some(hr)) => Some(hr),
-                (Some(l), Some(r)) => {
-                    if l.0 < r.0 {
-                        Some(l)
-                    } else {
-                        Some(r)
+impl Bvh {
+    /// Closest hit, using `stack` to remember the nodes still to visit. `stack` must hold at least
+    /// `self.depth` entries. Always inlined, as this is the hottest loop of the renderer.
+    #[inline(always)]
+    fn traverse(
+        &self,
+        stack: &mut [usize],
+        ray: &Ray,
+        t_min: f64,
+        mut t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        let mut closest = None;
+        let mut stack_len = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounding_box.hit(ray, t_min, t_max) {
+                match node.kind {
+                    NodeKind::Leaf { hittable } => {
+                        if let Some(hit) =
+                            self.hittables[hittable].hit(ray, t_min, t_max, time, rng)
+                        {
+                            // Nodes visited later must contain a closer hit to matter.
+                            t_max = hit.0;
+                            closest = Some(hit);
+                        }
+                    }
+                    NodeKind::Interior { second_child, axis } => {
+                        // Visit the child closer to the ray origin first, so hits found within it
+                        // let us skip more of the other one.
+                        let (near, far) = if ray.direction[axis].is_sign_negative() {
+                            (second_child, current + 1)
+                        } else {
+                            (current + 1, second_child)
+                        };
+                        stack[stack_len] = far;
+                        stack_len += 1;
+                        current = near;
+                        continue;
                     }
                 }
             }
+            if stack_len == 0 {
+                break closest;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        if self.depth <= TRAVERSAL_STACK_SIZE {
+            let mut stack = [0; TRAVERSAL_STACK_SIZE];
+            self.traverse(&mut stack, ray, t_min, t_max, time, rng)
         } else {
-            None
+            let mut stack = vec![0; self.depth];
+            self.traverse(&mut stack, ray, t_min, t_max, time, rng)
         }
     }
 }
 
-impl BoundingBox for BvhNode {
+impl BoundingBox for Bvh {
     fn bounding_box(&self, _exposure_time: f64) -> Aabb {
-        self.bounding_box
+        self.nodes[0].bounding_box
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::Lambertian,
+        shape::Sphere,
+        texture::Solid,
+        vec3::{Color, Vec3},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn sphere(center: Point, radius: f64) -> Box<dyn BoundedHittable> {
+        Box::new((
+            Sphere::new(center, radius),
+            Solid(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    fn random_spheres(rng: &mut StdRng, count: usize) -> HittableList {
+        (0..count)
+            .map(|_| sphere(Vec3::random(rng, -10., 10.), rng.gen_range(0.01, 1.)))
+            .collect()
+    }
+
+    /// Distance and hit point of the closest hit of every ray.
+    fn hits(hittable: &dyn Hittable, rays: &[Ray], rng: &mut StdRng) -> Vec<Option<[u64; 4]>> {
+        rays.iter()
+            .map(|ray| {
+                hittable
+                    .hit(ray, 0.001, f64::INFINITY, 0., rng)
+                    .map(|(t, hit)| {
+                        let point = hit.intersection.point;
+                        [
+                            t.to_bits(),
+                            point.x().to_bits(),
+                            point.y().to_bits(),
+                            point.z().to_bits(),
+                        ]
+                    })
+            })
+            .collect()
+    }
+
+    /// Compares the hierarchy built from `spheres` with testing every sphere.
+    fn assert_same_hits(spheres: impl Fn() -> HittableList, strategy: BvhStrategy) -> Bvh {
+        let mut rng = StdRng::seed_from_u64(3);
+        let rays: Vec<_> = (0..500)
+            .map(|_| {
+                Ray::new(
+                    Vec3::random(&mut rng, -15., 15.),
+                    Vec3::random(&mut rng, -1., 1.),
+                )
+            })
+            .collect();
+        let bvh = Bvh::new(spheres(), 0., strategy);
+        let expected = hits(&spheres(), &rays, &mut rng);
+        assert!(expected.iter().any(Option::is_some));
+        assert_eq!(hits(&bvh, &rays, &mut rng), expected);
+        bvh
+    }
+
+    #[test]
+    fn sah_finds_the_closest_hit() {
+        assert_same_hits(
+            || random_spheres(&mut StdRng::seed_from_u64(1), 300),
+            BvhStrategy::Sah,
+        );
+    }
+
+    #[test]
+    fn alternate_finds_the_closest_hit() {
+        assert_same_hits(
+            || random_spheres(&mut StdRng::seed_from_u64(1), 300),
+            BvhStrategy::Alternate,
+        );
+    }
+
+    #[test]
+    fn subtrees_built_in_parallel_find_the_closest_hit() {
+        let bvh = assert_same_hits(
+            || random_spheres(&mut StdRng::seed_from_u64(2), 2 * PARALLEL_BUILD_THRESHOLD),
+            BvhStrategy::Sah,
+        );
+        assert_eq!(bvh.nodes.len(), 4 * PARALLEL_BUILD_THRESHOLD - 1);
+    }
+
+    #[test]
+    fn identical_centroids_limit_the_depth() {
+        // Concentric spheres can not be separated by their centroids.
+        let bvh = assert_same_hits(
+            || {
+                (1..=1000)
+                    .map(|i| sphere(Point::new(1., 2., 3.), i as f64 * 0.01))
+                    .collect()
+            },
+            BvhStrategy::Sah,
+        );
+        assert!(bvh.depth <= MAX_STRATEGY_DEPTH + 10);
     }
 }
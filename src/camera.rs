@@ -3,6 +3,7 @@ use crate::{
     vec3::{cross, Point, Vec3},
 };
 use rand::Rng;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
 
 pub struct Camera {
     origin: Point,
@@ -16,6 +17,7 @@ pub struct Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         vertical_field_of_view: f64,
         aspect_ratio: f64,
@@ -61,10 +63,10 @@ impl Camera {
     /// # Parameters
     ///
     /// * `s`: horizontal coordinate of the projection plane going from left to right and zero to
-    /// one.
+    ///   one.
     /// * `t`: vertical coordinate of the projection plane going from bottom to top and zero to one.
     /// * `rng`: Used to generate random minor shifts in rays position for sampling.
-    pub fn get_ray(&self, s: f64, t: f64, rng: &mut impl Rng) -> Ray {
+    pub fn get_ray(&self, s: f64, t: f64, rng: &mut (impl Rng + ?Sized)) -> Ray {
         let rd = random_in_unit_disk(rng) * self.lens_radius;
         let offset = self.u * rd.x() + self.v * rd.y();
 
@@ -75,17 +77,24 @@ impl Camera {
     }
 
     /// Get a random point in time, between 0 and exposure time.
-    pub fn get_time(&self, rng: &mut impl Rng) -> f64 {
+    pub fn get_time(&self, rng: &mut (impl Rng + ?Sized)) -> f64 {
         rng.gen_range(0., self.exposure_time)
     }
 }
 
-fn random_in_unit_disk(rng: &mut impl Rng) -> Vec3 {
-    loop {
-        let x = rng.gen_range(-1., 1.);
-        let y: f64 = rng.gen_range(-1., 1.);
-        if x * x + y * y < 1. {
-            break Vec3::new(x, y, 0.);
-        }
+/// Uniformly distributed point on the unit disk. Uses Shirley's concentric mapping from the
+/// square, rather than rejection sampling, so it always consumes exactly two random numbers and
+/// keeps the distribution of stratified samples intact.
+fn random_in_unit_disk(rng: &mut (impl Rng + ?Sized)) -> Vec3 {
+    let x: f64 = rng.gen_range(-1., 1.);
+    let y: f64 = rng.gen_range(-1., 1.);
+    if x == 0. && y == 0. {
+        return Vec3::new(0., 0., 0.);
     }
+    let (radius, angle) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    Vec3::new(radius * angle.cos(), radius * angle.sin(), 0.)
 }
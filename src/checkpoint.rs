@@ -0,0 +1,277 @@
+use crate::{estimate::PixelEstimate, sampler::SamplerKind};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+};
+
+/// First line of every checkpoint file.
+const MAGIC: &str = "rtiow checkpoint 1";
+
+/// Everything which influences the samples of a pixel. A render can only be resumed with the same
+/// settings it has been started with, except for the number of samples per pixel of some samplers.
+#[derive(Serialize, Deserialize)]
+pub struct CheckpointSettings {
+    /// Hash of the scene description, see [`crate::persistence::SceneBuilder::content_hash`].
+    pub scene_hash: u64,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    #[serde(default)]
+    pub spectral: bool,
+}
+
+impl CheckpointSettings {
+    /// `true` if a render started with `self` can be continued with `other`. Raising the number of
+    /// samples per pixel refines a previous render, and lowering it finishes pixels early. Not
+    /// for samplers which spread the samples of a pixel according to their total count, since
+    /// they would repeat samples taken before.
+    pub fn can_resume_with(&self, other: &CheckpointSettings) -> bool {
+        let same_samples = self.samples_per_pixel == other.samples_per_pixel
+            || !self.sampler.depends_on_sample_count();
+        same_samples
+            && self.scene_hash == other.scene_hash
+            && self.image_width == other.image_width
+            && self.image_height == other.image_height
+            && self.max_depth == other.max_depth
+            && self.seed == other.seed
+            && self.sampler == other.sampler
+            && self.spectral == other.spectral
+    }
+}
+
+/// State of an unfinished render, so it can be resumed later.
+pub struct Checkpoint {
+    pub settings: CheckpointSettings,
+    /// Accumulated samples of each pixel, row by row starting at the top left.
+    pub estimates: Vec<PixelEstimate>,
+}
+
+impl Checkpoint {
+    /// The file starts with a text header, followed by the pixel estimates in binary.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", MAGIC)?;
+        writeln!(writer, "{}", serde_json::to_string(&self.settings)?)?;
+        for estimate in &self.estimates {
+            estimate.write_to(&mut writer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Can not open checkpoint '{}': {}", path.display(), error),
+            )
+        })?;
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if line.trim_end() != MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("'{}' is not a checkpoint file.", path.display()),
+            ));
+        }
+        line.clear();
+        reader.read_line(&mut line)?;
+        let settings: CheckpointSettings = serde_json::from_str(&line)?;
+        let estimates = (0..settings.image_width * settings.image_height)
+            .map(|_| PixelEstimate::read_from(&mut reader))
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            settings,
+            estimates,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        random_scenes,
+        render::{render, RenderSettings},
+        tile::Framebuffer,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{env, fs, path::PathBuf};
+
+    fn settings() -> CheckpointSettings {
+        CheckpointSettings {
+            scene_hash: 0x1234_5678_9abc_def0,
+            image_width: 6,
+            image_height: 4,
+            samples_per_pixel: 8,
+            max_depth: 5,
+            seed: 42,
+            sampler: SamplerKind::Sobol,
+            spectral: true,
+        }
+    }
+
+    fn render_settings(settings: &CheckpointSettings) -> RenderSettings {
+        RenderSettings {
+            image_width: settings.image_width,
+            image_height: settings.image_height,
+            samples_per_pixel: settings.samples_per_pixel,
+            max_depth: settings.max_depth,
+            seed: settings.seed,
+            noise_threshold: None,
+            sampler: settings.sampler,
+            spectral: settings.spectral,
+            tile_size: 4,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("rtiow-test-{}-{}", std::process::id(), name))
+    }
+
+    fn save_and_load(checkpoint: &Checkpoint, name: &str) -> io::Result<Checkpoint> {
+        let path = temp_path(name);
+        checkpoint.save(&path)?;
+        let loaded = Checkpoint::load(&path);
+        fs::remove_file(&path)?;
+        loaded
+    }
+
+    fn bits(estimates: &[PixelEstimate]) -> Vec<(u32, [u64; 3])> {
+        estimates
+            .iter()
+            .map(|estimate| {
+                let mean = estimate.mean();
+                (
+                    estimate.count(),
+                    [mean.x().to_bits(), mean.y().to_bits(), mean.z().to_bits()],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_keeps_settings_and_estimates() {
+        let estimates = (0..24)
+            .map(|i| {
+                let mut estimate = PixelEstimate::new();
+                for j in 0..i % 5 {
+                    estimate.add(crate::vec3::Color::new(i as f64, j as f64 * 0.1, 1. / 3.));
+                }
+                estimate
+            })
+            .collect();
+        let checkpoint = Checkpoint {
+            settings: settings(),
+            estimates,
+        };
+        let loaded = save_and_load(&checkpoint, "round-trip").unwrap();
+
+        assert!(loaded.settings.can_resume_with(&checkpoint.settings));
+        assert_eq!(loaded.settings.samples_per_pixel, 8);
+        assert_eq!(bits(&loaded.estimates), bits(&checkpoint.estimates));
+        for (loaded, saved) in loaded.estimates.iter().zip(&checkpoint.estimates) {
+            assert_eq!(loaded.is_converged(0.1), saved.is_converged(0.1));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let checkpoint = Checkpoint {
+            settings: settings(),
+            estimates: vec![PixelEstimate::new(); 23],
+        };
+        let error = save_and_load(&checkpoint, "truncated").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = temp_path("other");
+        fs::write(&path, "{}\n").unwrap();
+        let error = Checkpoint::load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn only_the_sample_count_may_change() {
+        let mut started = settings();
+        started.sampler = SamplerKind::Halton;
+        let mut other = settings();
+        other.sampler = SamplerKind::Halton;
+        other.samples_per_pixel = 1000;
+        assert!(started.can_resume_with(&other));
+        other.seed += 1;
+        assert!(!started.can_resume_with(&other));
+        let mut other = settings();
+        other.spectral = false;
+        assert!(!started.can_resume_with(&other));
+    }
+
+    #[test]
+    fn sample_count_of_stratified_and_sobol_is_fixed() {
+        for &sampler in &[SamplerKind::Stratified, SamplerKind::Sobol] {
+            let mut started = settings();
+            started.sampler = sampler;
+            let mut other = settings();
+            other.sampler = sampler;
+            assert!(started.can_resume_with(&other));
+            other.samples_per_pixel += 1;
+            assert!(!started.can_resume_with(&other));
+        }
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_render() {
+        let scene = random_scenes::spheres(&mut StdRng::seed_from_u64(1), 1.5)
+            .build()
+            .unwrap();
+        // Stratified and Sobol samplers distribute the samples by their total count.
+        for &sampler in &[SamplerKind::Independent, SamplerKind::Halton] {
+            let mut settings = settings();
+            settings.sampler = sampler;
+            let framebuffer = Framebuffer::new(settings.image_width, settings.image_height);
+            render(
+                &scene,
+                &render_settings(&settings),
+                &framebuffer,
+                |_progress| (),
+                || false,
+            );
+            let uninterrupted = framebuffer.into_estimates();
+
+            settings.samples_per_pixel = 3;
+            let framebuffer = Framebuffer::new(settings.image_width, settings.image_height);
+            render(
+                &scene,
+                &render_settings(&settings),
+                &framebuffer,
+                |_progress| (),
+                || false,
+            );
+            let checkpoint = Checkpoint {
+                settings,
+                estimates: framebuffer.into_estimates(),
+            };
+            let mut resumed = save_and_load(&checkpoint, "resume").unwrap();
+            resumed.settings.samples_per_pixel = 8;
+            let framebuffer =
+                Framebuffer::from_estimates(resumed.settings.image_width, resumed.estimates);
+            render(
+                &scene,
+                &render_settings(&resumed.settings),
+                &framebuffer,
+                |_progress| (),
+                || false,
+            );
+
+            assert_eq!(bits(&framebuffer.into_estimates()), bits(&uninterrupted));
+        }
+    }
+}
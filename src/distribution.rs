@@ -0,0 +1,108 @@
+/// Piecewise constant probability distribution over the interval [0, 1). Each of the `n` equally
+/// sized segments is chosen with a probability proportional to its weight.
+pub struct Distribution1d {
+    weights: Vec<f64>,
+    /// Cumulative distribution function. Has one more element than `weights`, starting with `0`
+    /// and ending in `1`.
+    cdf: Vec<f64>,
+    /// Integral of the weights over [0, 1).
+    integral: f64,
+}
+
+impl Distribution1d {
+    /// Weights must not be negative. If all of them are zero, the distribution falls back to
+    /// uniform.
+    pub fn new(weights: Vec<f64>) -> Self {
+        let n = weights.len() as f64;
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        cdf.push(0.);
+        for weight in &weights {
+            cdf.push(cdf.last().unwrap() + weight / n);
+        }
+        let integral = *cdf.last().unwrap();
+        if integral > 0. {
+            for value in &mut cdf {
+                *value /= integral;
+            }
+        } else {
+            for (index, value) in cdf.iter_mut().enumerate() {
+                *value = index as f64 / n;
+            }
+        }
+        Self {
+            weights,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniformly distributed `u` in [0, 1) onto this distribution. Returns the sampled value
+    /// in [0, 1) together with the index of the segment it lies in.
+    pub fn sample(&self, u: f64) -> (f64, usize) {
+        // Index of the last cdf entry smaller or equal to u. Taking the last one skips segments with
+        // zero weight.
+        let index = (self.cdf.partition_point(|&value| value <= u) - 1).min(self.weights.len() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0. {
+            (u - self.cdf[index]) / width
+        } else {
+            0.
+        };
+        ((index as f64 + offset) / self.weights.len() as f64, index)
+    }
+
+    /// Probability density of sampling a value in segment `index`.
+    pub fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0. {
+            self.weights[index] / self.integral
+        } else {
+            1.
+        }
+    }
+}
+
+/// Piecewise constant probability distribution over the unit square, e.g. to pick pixels of an
+/// image proportional to their brightness.
+pub struct Distribution2d {
+    /// One distribution for each row, picking the column.
+    conditional: Vec<Distribution1d>,
+    /// Picks the row.
+    marginal: Distribution1d,
+}
+
+impl Distribution2d {
+    /// `weights` are stored in rows of length `width`.
+    pub fn new(weights: &[f64], width: usize) -> Self {
+        let conditional: Vec<_> = weights
+            .chunks(width)
+            .map(|row| Distribution1d::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1d::new(conditional.iter().map(|c| c.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Maps two uniformly distributed values onto the distribution. Returns the sampled point as
+    /// `(column, row)` coordinates within [0, 1) together with its probability density.
+    pub fn sample(&self, u: f64, v: f64) -> ((f64, f64), f64) {
+        let (y, row) = self.marginal.sample(v);
+        let (x, column) = self.conditional[row].sample(u);
+        let pdf = self.marginal.pdf(row) * self.conditional[row].pdf(column);
+        ((x, y), pdf)
+    }
+
+    /// Probability density of sampling the point `(x, y)` of the unit square.
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let rows = self.conditional.len();
+        let row = ((y * rows as f64) as usize).min(rows - 1);
+        let columns = self.conditional[row].weights.len();
+        let column = ((x * columns as f64) as usize).min(columns - 1);
+        self.marginal.pdf(row) * self.conditional[row].pdf(column)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    distribution::Distribution2d,
+    image_file::load_linear,
+    vec3::{luminance, Color, Vec3},
+};
+use rand::Rng;
+use std::{f64::consts::PI, io, path::Path};
+
+/// An equirectangular (latitude / longitude) image surrounding the whole scene. Used for image
+/// based lighting.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    /// Linear radiance stored row by row, starting with the row looking straight up.
+    pixels: Vec<Color>,
+    /// Rotation around the vertical axis in radians.
+    rotation: f64,
+    /// Pixels are chosen proportional to the light they contribute, so bright light sources within
+    /// the map (e.g. the sun) are found by the scatter path more often.
+    distribution: Distribution2d,
+}
+
+impl EnvironmentMap {
+    /// Loads the map from any file supported by [`load_linear`]. `rotation` is given in degrees.
+    /// All pixels are scaled by `intensity`.
+    pub fn from_path(path: &Path, rotation: f64, intensity: f64) -> io::Result<Self> {
+        let (width, height, mut pixels) = load_linear(path)?;
+        for pixel in &mut pixels {
+            *pixel *= intensity;
+        }
+        Ok(Self::new(width, height, pixels, rotation.to_radians()))
+    }
+
+    fn new(width: usize, height: usize, pixels: Vec<Color>, rotation: f64) -> Self {
+        // Weight each pixel by the solid angle it covers. Rows near the poles are squeezed
+        // together.
+        let weights: Vec<_> = pixels
+            .iter()
+            .enumerate()
+            .map(|(index, pixel)| {
+                let row = index / width;
+                let sin_theta = (PI * (row as f64 + 0.5) / height as f64).sin();
+                luminance(pixel) * sin_theta
+            })
+            .collect();
+        let distribution = Distribution2d::new(&weights, width);
+        Self {
+            width,
+            height,
+            pixels,
+            rotation,
+            distribution,
+        }
+    }
+
+    /// Radiance arriving from `direction`.
+    pub fn color(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.map_coordinates(direction);
+        let column = ((u * self.width as f64) as usize).min(self.width - 1);
+        let row = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[row * self.width + column]
+    }
+
+    /// Random direction, chosen proportional to the radiance arriving from it.
+    pub fn sample_direction(&self, rng: &mut (impl Rng + ?Sized)) -> Vec3 {
+        let ((u, v), _pdf) = self
+            .distribution
+            .sample(rng.gen_range(0., 1.), rng.gen_range(0., 1.));
+        self.direction_at(u, v)
+    }
+
+    /// Probability density (with respect to solid angle) of `sample_direction` returning
+    /// `direction`.
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.map_coordinates(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        // The map coordinates span 2 PI horizontally and PI vertically.
+        self.distribution.pdf(u, v) / (2. * PI * PI * sin_theta)
+    }
+
+    /// Horizontal `u` and vertical `v` coordinates in [0, 1]. `v` is zero looking straight up.
+    fn map_coordinates(&self, direction: &Vec3) -> (f64, f64) {
+        let direction = direction.unit();
+        let theta = direction.y().clamp(-1., 1.).acos();
+        let phi = direction.x().atan2(-direction.z()) + self.rotation;
+        let u = (phi / (2. * PI) + 0.5).rem_euclid(1.);
+        let v = theta / PI;
+        (u, v)
+    }
+
+    fn direction_at(&self, u: f64, v: f64) -> Vec3 {
+        let phi = (u - 0.5) * 2. * PI - self.rotation;
+        let theta = v * PI;
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+}
@@ -0,0 +1,94 @@
+use crate::vec3::{luminance, Color};
+use std::io::{self, Read, Write};
+
+/// Running estimate of the color of a single pixel. Besides the mean it tracks the variance of the
+/// luminance of the samples (using Welford's algorithm), so we can tell how noisy the pixel still
+/// is.
+#[derive(Clone, Copy)]
+pub struct PixelEstimate {
+    sum: Color,
+    count: u32,
+    mean_luminance: f64,
+    /// Sum of squared differences from the mean luminance.
+    m2: f64,
+}
+
+impl Default for PixelEstimate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PixelEstimate {
+    pub fn new() -> Self {
+        Self {
+            sum: Color::ZERO,
+            count: 0,
+            mean_luminance: 0.,
+            m2: 0.,
+        }
+    }
+
+    pub fn add(&mut self, sample: Color) {
+        self.sum += sample;
+        self.count += 1;
+        let value = luminance(&sample);
+        let delta = value - self.mean_luminance;
+        self.mean_luminance += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean_luminance);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Average of all samples. Black if there are none.
+    pub fn mean(&self) -> Color {
+        if self.count == 0 {
+            Color::ZERO
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// `true` if the 95% confidence interval of the mean luminance, relative to the mean itself, is
+    /// smaller than `threshold`. Dark pixels are judged against an absolute error instead, since
+    /// a relative one would never be reached for black.
+    pub fn is_converged(&self, threshold: f64) -> bool {
+        if self.count < 2 {
+            return false;
+        }
+        let variance = self.m2 / (self.count - 1) as f64;
+        let half_width = 1.96 * (variance / self.count as f64).sqrt();
+        half_width <= threshold * self.mean_luminance.max(0.01)
+    }
+
+    /// Writes the complete state in a fixed size little endian binary format.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        for value in self.sum.iter() {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&self.count.to_le_bytes())?;
+        writer.write_all(&self.mean_luminance.to_le_bytes())?;
+        writer.write_all(&self.m2.to_le_bytes())
+    }
+
+    /// Reads an estimate written by [`Self::write_to`].
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let sum = Color::new(read_f64(reader)?, read_f64(reader)?, read_f64(reader)?);
+        let mut count = [0; 4];
+        reader.read_exact(&mut count)?;
+        Ok(Self {
+            sum,
+            count: u32::from_le_bytes(count),
+            mean_luminance: read_f64(reader)?,
+            m2: read_f64(reader)?,
+        })
+    }
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
@@ -1,8 +1,11 @@
 use crate::{
+    bounding_box::{Aabb, BoundingBox},
     ray::Ray,
     shape::{Puncture, Shape},
     texture::Texture,
 };
+use rand::RngCore;
+use std::sync::Arc;
 
 pub struct Hit<'m> {
     pub intersection: Puncture,
@@ -19,20 +22,34 @@ impl<'m> Hit<'m> {
 }
 
 pub trait Hittable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, time: f64) -> Option<(f64, Hit)>;
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)>;
 }
 
 impl<T> Hittable for Vec<T>
 where
     T: Hittable,
 {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, time: f64) -> Option<(f64, Hit)> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
         self.iter().fold(None, |rec, hittable| {
             let closest_so_far = rec
                 .as_ref()
                 .map(|(distance, _hit)| *distance)
                 .unwrap_or(t_max);
-            if let Some(rec) = hittable.hit(ray, t_min, closest_so_far, time) {
+            if let Some(rec) = hittable.hit(ray, t_min, closest_so_far, time, rng) {
                 Some(rec)
             } else {
                 rec
@@ -45,8 +62,32 @@ impl<T> Hittable for Box<T>
 where
     T: Hittable + ?Sized,
 {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, time: f64) -> Option<(f64, Hit)> {
-        self.as_ref().hit(ray, t_min, t_max, time)
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        self.as_ref().hit(ray, t_min, t_max, time, rng)
+    }
+}
+
+/// Allows the same geometry, e.g. the hierarchy of a mesh, to be placed multiple times.
+impl<T> Hittable for Arc<T>
+where
+    T: Hittable + ?Sized,
+{
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        self.as_ref().hit(ray, t_min, t_max, time, rng)
     }
 }
 
@@ -55,7 +96,14 @@ where
     S: Shape,
     T: Texture,
 {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, _time: f64) -> Option<(f64, Hit)> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        _time: f64,
+        _rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
         self.0
             .intersect(ray, t_min, t_max)
             .map(|(distance, intersection)| {
@@ -64,3 +112,44 @@ where
             })
     }
 }
+
+/// Shows the surfaces of the inner hittable with `texture`, regardless of the textures they have
+/// been built with. Lets placements of shared geometry differ in their material.
+pub struct Textured<H, T> {
+    inner: H,
+    texture: T,
+}
+
+impl<H, T> Textured<H, T> {
+    pub fn new(inner: H, texture: T) -> Self {
+        Self { inner, texture }
+    }
+}
+
+impl<H, T> Hittable for Textured<H, T>
+where
+    H: Hittable,
+    T: Texture,
+{
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        self.inner
+            .hit(ray, t_min, t_max, time, rng)
+            .map(|(distance, hit)| (distance, Hit::new(hit.intersection, &self.texture)))
+    }
+}
+
+impl<B, T> BoundingBox for Textured<B, T>
+where
+    B: BoundingBox,
+{
+    fn bounding_box(&self, exposure_time: f64) -> Aabb {
+        self.inner.bounding_box(exposure_time)
+    }
+}
@@ -0,0 +1,358 @@
+use crate::vec3::Color;
+use image::{hdr::HdrDecoder, ImageError};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+/// Loads an image as linear RGB values. Returns width, height and pixels row by row, starting with
+/// the top row. Radiance `.hdr` and `.pfm` files are read as floating point values. Any other
+/// format supported by the `image` crate is assumed to be sRGB encoded. Fails for images without
+/// any pixels.
+pub fn load_linear(path: &Path) -> io::Result<(usize, usize, Vec<Color>)> {
+    let (width, height, pixels) = match lowercase_extension(path).as_deref() {
+        Some("hdr") => load_hdr(path),
+        Some("pfm") => load_pfm(path),
+        _ => load_ldr(path),
+    }?;
+    if width == 0 || height == 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Image '{}' does not contain any pixels.", path.display()),
+        ));
+    }
+    Ok((width, height, pixels))
+}
+
+fn load_hdr(path: &Path) -> io::Result<(usize, usize, Vec<Color>)> {
+    let reader = BufReader::new(File::open(path)?);
+    let decoder = HdrDecoder::new(reader).map_err(into_io_error)?;
+    let metadata = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()
+        .map_err(into_io_error)?
+        .into_iter()
+        .map(|pixel| Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64))
+        .collect();
+    Ok((metadata.width as usize, metadata.height as usize, pixels))
+}
+
+/// Portable float map. Three channel (`PF`) and grayscale (`Pf`) variants are supported.
+fn load_pfm(path: &Path) -> io::Result<(usize, usize, Vec<Color>)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "Invalid PFM header.");
+    let mut read_line = || -> io::Result<String> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        Ok(line.trim().to_owned())
+    };
+    let channels = match read_line()?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid()),
+    };
+    let dimensions = read_line()?;
+    let mut dimensions = dimensions.split_whitespace().map(|d| d.parse::<usize>());
+    let (width, height) = match (dimensions.next(), dimensions.next()) {
+        (Some(Ok(width)), Some(Ok(height))) => (width, height),
+        _ => return Err(invalid()),
+    };
+    // A negative scale indicates little endian byte order.
+    let scale: f64 = read_line()?.parse().map_err(|_| invalid())?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let size = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(invalid)?;
+    let floats: Vec<f64> = bytes
+        .chunks_exact(4)
+        .map(|chunk| {
+            let chunk = [chunk[0], chunk[1], chunk[2], chunk[3]];
+            if scale < 0. {
+                f32::from_le_bytes(chunk) as f64
+            } else {
+                f32::from_be_bytes(chunk) as f64
+            }
+        })
+        .collect();
+    if floats.len() < size {
+        return Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "PFM file contains less pixels than its header states.",
+        ));
+    }
+    // Rows are stored bottom to top.
+    let pixels = (0..height)
+        .rev()
+        .flat_map(|row| (0..width).map(move |column| row * width + column))
+        .map(|index| {
+            let pixel = &floats[index * channels..(index + 1) * channels];
+            if channels == 3 {
+                Color::new(pixel[0], pixel[1], pixel[2])
+            } else {
+                Color::new(pixel[0], pixel[0], pixel[0])
+            }
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
+
+fn load_ldr(path: &Path) -> io::Result<(usize, usize, Vec<Color>)> {
+    let image = image::open(path).map_err(into_io_error)?.into_rgb();
+    let srgb_to_linear = |value: u8| {
+        let value = value as f64 / 255.;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let pixels = image
+        .pixels()
+        .map(|pixel| {
+            Color::new(
+                srgb_to_linear(pixel[0]),
+                srgb_to_linear(pixel[1]),
+                srgb_to_linear(pixel[2]),
+            )
+        })
+        .collect();
+    Ok((image.width() as usize, image.height() as usize, pixels))
+}
+
+/// `true` for file extensions [`save_linear`] can write.
+pub fn is_float_format(path: &Path) -> bool {
+    matches!(
+        lowercase_extension(path).as_deref(),
+        Some("exr") | Some("pfm")
+    )
+}
+
+/// Saves linear RGB values without any clamping or quantization, as 32 bit floats. `pixels` are
+/// stored row by row, starting with the top row. The format is chosen by the file extension and
+/// must be either `.exr` or `.pfm`.
+pub fn save_linear(path: &Path, width: usize, pixels: &[Color]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let height = pixels.len() / width;
+    match lowercase_extension(path).as_deref() {
+        Some("exr") => write_exr(&mut writer, width, height, pixels)?,
+        Some("pfm") => write_pfm(&mut writer, width, height, pixels)?,
+        _ => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Floating point output requires an '.exr' or '.pfm' file extension.",
+            ))
+        }
+    }
+    writer.flush()
+}
+
+fn write_pfm(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    // Negative scale announces little endian byte order.
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+    // Rows are stored bottom to top.
+    for row in pixels.chunks(width).rev() {
+        for pixel in row {
+            for &channel in pixel.iter() {
+                writer.write_all(&(channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Single part, scan line based OpenEXR file without compression. Channels are stored as 32 bit
+/// floats.
+fn write_exr(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    // Channels must be listed in alphabetical order.
+    const CHANNELS: [(&str, usize); 3] = [("B", 2), ("G", 1), ("R", 0)];
+    const FLOAT: i32 = 2;
+
+    fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+
+    let mut channel_list = Vec::new();
+    for (name, _) in &CHANNELS {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&FLOAT.to_le_bytes());
+        // Linear flag and three reserved bytes, followed by x and y sampling.
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+    let mut window = Vec::new();
+    for value in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Magic number and version 2 (single part scan line file).
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    writer.write_all(&header)?;
+
+    // Without compression every block holds exactly one scan line. The offset table points to the
+    // start of each block.
+    let line_size = width * CHANNELS.len() * 4;
+    let block_size = 8 + line_size;
+    let first_block = header.len() + height * 8;
+    for row in 0..height {
+        writer.write_all(&((first_block + row * block_size) as u64).to_le_bytes())?;
+    }
+    for (row, line) in pixels.chunks(width).enumerate() {
+        writer.write_all(&(row as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        for (_, channel) in &CHANNELS {
+            for pixel in line {
+                writer.write_all(&(pixel[*channel] as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lowercase_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+fn into_io_error(error: ImageError) -> io::Error {
+    match error {
+        ImageError::IoError(e) => e,
+        other => io::Error::new(ErrorKind::InvalidData, other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::TryInto, env, fs};
+
+    fn pixels() -> Vec<Color> {
+        // Values outside of [0, 1] must survive, as well as the order of rows and channels.
+        vec![
+            Color::new(0., 0.25, 1.5),
+            Color::new(100., 0.5, 0.),
+            Color::new(-1., 2., 3.),
+            Color::new(0.125, 0.0625, 1e-3),
+            Color::new(4., 5., 6.),
+            Color::new(7., 8., 9.),
+        ]
+    }
+
+    fn read_i32(bytes: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_bits(read_i32(bytes, offset) as u32)
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let path = env::temp_dir().join(format!("rtiow-test-{}.pfm", std::process::id()));
+        save_linear(&path, 2, &pixels()).unwrap();
+        let loaded = load_linear(&path);
+        fs::remove_file(&path).unwrap();
+        let (width, height, loaded) = loaded.unwrap();
+        assert_eq!((width, height), (2, 3));
+        for (loaded, expected) in loaded.iter().zip(&pixels()) {
+            for channel in 0..3 {
+                assert_eq!(loaded[channel], expected[channel] as f32 as f64);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_offsets_point_to_scan_lines() {
+        let (width, height) = (2, 3);
+        let mut bytes = Vec::new();
+        write_exr(&mut bytes, width, height, &pixels()).unwrap();
+        assert_eq!(bytes[..4], [0x76, 0x2f, 0x31, 0x01]);
+
+        let line_size = width * 3 * 4;
+        // The offset table sits between the header and the scan lines.
+        let first_offset = bytes.len() - height * (8 + line_size) - height * 8;
+        for row in 0..height {
+            let entry = first_offset + row * 8;
+            let offset = u64::from_le_bytes(bytes[entry..entry + 8].try_into().unwrap()) as usize;
+            assert_eq!(read_i32(&bytes, offset), row as i32);
+            assert_eq!(read_i32(&bytes, offset + 4), line_size as i32);
+            // Channels are stored one after another in the order B, G, R.
+            let pixel = &pixels()[row * width + 1];
+            let values = offset + 8;
+            assert_eq!(read_f32(&bytes, values + 4) as f64, pixel.z() as f32 as f64);
+            assert_eq!(
+                read_f32(&bytes, values + 12) as f64,
+                pixel.y() as f32 as f64
+            );
+            assert_eq!(
+                read_f32(&bytes, values + 20) as f64,
+                pixel.x() as f32 as f64
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_pfm_dimensions() {
+        for (name, header) in &[
+            ("empty", "PF\n0 0\n-1.0\n"),
+            ("no-rows", "PF\n4 0\n-1.0\n"),
+            ("overflow", "PF\n18446744073709551615 2\n-1.0\n"),
+        ] {
+            let path =
+                env::temp_dir().join(format!("rtiow-test-{}-{}.pfm", std::process::id(), name));
+            fs::write(&path, header).unwrap();
+            let error = load_linear(&path).err().unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_float_formats() {
+        let path = env::temp_dir().join(format!("rtiow-test-{}.png", std::process::id()));
+        let error = save_linear(&path, 2, &pixels()).unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+}
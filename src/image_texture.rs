@@ -0,0 +1,73 @@
+use crate::{
+    image_file::load_linear,
+    material::{Lambertian, Material, ScatterResult},
+    shape::Puncture,
+    texture::Texture,
+    vec3::{Color, Vec3},
+};
+use rand::RngCore;
+use std::{io, path::Path};
+
+/// Diffuse surface with its albedo looked up from an image via the texture coordinates of the
+/// shape. Coordinates outside of [0, 1] repeat the image.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Linear colors, row by row starting with the top row.
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let (width, height, pixels) = load_linear(path)?;
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn material(&self, punctured: &Puncture) -> Lambertian {
+        let (u, v) = punctured.texture_coordiantes;
+        let column = (u.rem_euclid(1.) * self.width as f64) as usize;
+        // Texture coordinates start at the bottom of the image.
+        let row = ((1. - v.rem_euclid(1.)) * self.height as f64) as usize;
+        let column = column.min(self.width - 1);
+        let row = row.min(self.height - 1);
+        Lambertian::new(self.pixels[row * self.width + column])
+    }
+}
+
+impl Texture for ImageTexture {
+    fn scatter(
+        &self,
+        rng: &mut dyn RngCore,
+        punctured: &Puncture,
+        incoming: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        self.material(punctured).scatter(
+            rng,
+            incoming,
+            &punctured.normal,
+            punctured.front_face,
+            wavelength,
+        )
+    }
+
+    fn evaluate(
+        &self,
+        punctured: &Puncture,
+        incoming: &Vec3,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        self.material(punctured).evaluate(
+            incoming,
+            &punctured.normal,
+            punctured.front_face,
+            direction,
+            wavelength,
+        )
+    }
+}
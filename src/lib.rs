@@ -0,0 +1,91 @@
+//! A ray tracer based on the methods presented in the Ray Tracing in one Weekend tutorial.
+//!
+//! Rendering an image takes three steps: Describe a scene with a [`SceneBuilder`] (usually loaded
+//! from a JSON file) and [`build`](SceneBuilder::build) it, [`render`] it into a [`Framebuffer`]
+//! and finally [`save_image`].
+//!
+//! ```no_run
+//! use rtiow::{render, save_image, Framebuffer, RenderSettings, SamplerKind, SceneBuilder};
+//!
+//! let scene_builder = SceneBuilder::from_path("scene.json")?;
+//! let scene = scene_builder.build()?;
+//! let settings = RenderSettings {
+//!     image_width: 384,
+//!     image_height: 216,
+//!     samples_per_pixel: 100,
+//!     max_depth: 50,
+//!     seed: 42,
+//!     noise_threshold: None,
+//!     sampler: SamplerKind::Independent,
+//!     spectral: false,
+//!     tile_size: 16,
+//! };
+//! let framebuffer = Framebuffer::new(settings.image_width, settings.image_height);
+//! render(&scene, &settings, &framebuffer, |_progress| (), || false);
+//! save_image(
+//!     &framebuffer.colors(),
+//!     settings.image_width,
+//!     "image.png".as_ref(),
+//!     &scene_builder.display,
+//! )?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+// https://raytracing.github.io/books/RayTracingInOneWeekend.html
+// Online ppm viewer: http://cs.rhodes.edu/welshc/COMP141_F16/ppmReader.html
+mod background;
+mod bounding_box;
+mod bvh;
+mod camera;
+mod checkpoint;
+mod distribution;
+mod environment;
+mod estimate;
+mod hittable;
+mod image_file;
+mod image_texture;
+mod lights;
+mod material;
+mod medium;
+mod mesh;
+mod moving;
+mod output;
+mod perlin;
+mod persistence;
+mod random_scenes;
+mod ray;
+mod render;
+mod renderable;
+mod sampler;
+mod scene;
+mod shape;
+mod spectrum;
+mod texture;
+mod tile;
+mod tone_mapping;
+mod transform;
+mod vec3;
+
+// Scene description. Usually deserialized from JSON, but can also be put together in code.
+pub use crate::{
+    bvh::BvhStrategy,
+    material::{Dispersion, PrincipledParameters, RefractiveIndex},
+    persistence::{
+        Absorption, BackgroundBuilder, CameraBuilder, ConductorIor, HittableBuilder, Placement,
+        SceneBuilder, ShapeBuilder, SurfaceBuilder, VolumeBuilder,
+    },
+    random_scenes::spheres as random_spheres,
+    tone_mapping::{Display, ToneMapper},
+    vec3::{Color, Point, Vec3},
+};
+
+// Rendering and output.
+pub use crate::{
+    checkpoint::{Checkpoint, CheckpointSettings},
+    estimate::PixelEstimate,
+    output::save_image,
+    render::{render, Progress, RenderSettings},
+    sampler::SamplerKind,
+    scene::Scene,
+    tile::Framebuffer,
+};
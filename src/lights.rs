@@ -0,0 +1,134 @@
+use crate::{
+    ray::Ray,
+    shape::BoundedShape,
+    transform::Matrix4,
+    vec3::{Color, Point, Vec3},
+};
+use rand::{Rng, RngCore};
+use std::sync::Arc;
+
+/// A glowing object in the scene, which can be aimed at directly.
+pub struct Light {
+    shape: Arc<dyn BoundedShape>,
+    /// Radiance emitted by every point of the surface.
+    emit: Color,
+    /// From object to world coordinates, together with its inverse. Only rotation, translation and
+    /// uniform scaling are allowed, so densities with respect to solid angle are the same in
+    /// object and world coordinates.
+    transformation: Option<(Matrix4, Matrix4)>,
+    /// Distance travelled per unit of time. Applied after `transformation`.
+    velocity: Vec3,
+}
+
+impl Light {
+    pub fn new(shape: Arc<dyn BoundedShape>, emit: Color) -> Self {
+        Self {
+            shape,
+            emit,
+            transformation: None,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Places the light like [`crate::transform::Transform`] and [`crate::moving::Moving`] place
+    /// the object it belongs to. `None` if `matrix` is singular or distorts angles, e.g. by
+    /// scaling non uniformly.
+    pub fn placed(mut self, matrix: Option<Matrix4>, velocity: Option<Vec3>) -> Option<Self> {
+        if let Some(matrix) = matrix {
+            if !matrix.preserves_angles() {
+                return None;
+            }
+            self.transformation = Some((matrix, matrix.inverse()?));
+        }
+        self.velocity = velocity.unwrap_or(Vec3::ZERO);
+        Some(self)
+    }
+
+    fn to_object(&self, point: &Point, time: f64) -> Point {
+        let point = *point - self.velocity * time;
+        match &self.transformation {
+            Some((_, inverse)) => inverse.transform_point(&point),
+            None => point,
+        }
+    }
+
+    fn to_world(&self, point: &Point, time: f64) -> Point {
+        let point = match &self.transformation {
+            Some((matrix, _)) => matrix.transform_point(point),
+            None => *point,
+        };
+        point + self.velocity * time
+    }
+
+    /// See [`crate::shape::Shape::sample_towards`].
+    fn sample_towards(
+        &self,
+        origin: &Point,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(Point, f64)> {
+        let (point, pdf) = self
+            .shape
+            .sample_towards(&self.to_object(origin, time), rng)?;
+        Some((self.to_world(&point, time), pdf))
+    }
+
+    /// See [`crate::shape::Shape::pdf_towards`].
+    fn pdf_towards(&self, ray: &Ray, t_max: f64, time: f64) -> f64 {
+        // The direction is not normalized, so `t_max` stays the same in object coordinates.
+        let direction = match &self.transformation {
+            Some((_, inverse)) => inverse.transform_vector(&ray.direction),
+            None => ray.direction,
+        };
+        let ray = Ray::new(self.to_object(&ray.origin, time), direction);
+        self.shape.pdf_towards(&ray, t_max)
+    }
+}
+
+/// All lights in the scene, which can be sampled explicitly (next event estimation). Glowing
+/// objects not part of this list still illuminate the scene, but are only found by chance.
+pub struct Lights(Vec<Light>);
+
+impl Lights {
+    pub fn new(lights: Vec<Light>) -> Self {
+        Self(lights)
+    }
+
+    /// Picks a random light and a point on it. Returns the direction from `origin` towards that
+    /// point (not normalized, i.e. `origin + direction` is the point on the light), the radiance
+    /// emitted by the light and the probability density of the direction with respect to solid
+    /// angle.
+    pub fn sample(
+        &self,
+        origin: &Point,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(Vec3, Color, f64)> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let light = &self.0[rng.gen_range(0, self.0.len())];
+        let (point, pdf) = light.sample_towards(origin, time, rng)?;
+        if pdf <= 0. || !pdf.is_finite() {
+            return None;
+        }
+        Some((point - *origin, light.emit, pdf / self.0.len() as f64))
+    }
+
+    /// Probability density of `sample` choosing the direction of `ray`, given that the ray hits a
+    /// light at parameter `t_hit`. Lights further away than `t_hit` are occluded and can not be
+    /// responsible for the hit.
+    pub fn pdf(&self, ray: &Ray, t_hit: f64, time: f64) -> f64 {
+        if self.0.is_empty() {
+            return 0.;
+        }
+        // Allow for some numerical error in the intersection of the light with the ray.
+        let t_max = t_hit * 1.0001;
+        let sum: f64 = self
+            .0
+            .iter()
+            .map(|light| light.pdf_towards(ray, t_max, time))
+            .sum();
+        sum / self.0.len() as f64
+    }
+}
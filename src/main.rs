@@ -1,35 +1,19 @@
-// https://raytracing.github.io/books/RayTracingInOneWeekend.html
-// Online ppm viewer: http://cs.rhodes.edu/welshc/COMP141_F16/ppmReader.html
-mod bounding_box;
-mod bvh;
-mod camera;
-mod hittable;
-mod material;
-mod moving;
-mod output;
-mod perlin;
-mod persistence;
-mod random_scenes;
-mod ray;
-mod renderable;
-mod scene;
-mod shape;
-mod texture;
-mod vec3;
-
-use crate::{output::save_image, persistence::SceneBuilder, vec3::Color};
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::thread_rng;
-use rayon::prelude::*;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use rtiow::{
+    random_spheres, render, save_image, BvhStrategy, Checkpoint, CheckpointSettings, Framebuffer,
+    Progress, RenderSettings, SamplerKind, SceneBuilder, ToneMapper,
+};
 use structopt::StructOpt;
 
 use std::{
-    io,
+    fs, io,
     path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Instant,
 };
 
 /// A ray tracer based on the methods presented in the Ray Tracing in one Weekend tutorial.
@@ -56,6 +40,50 @@ struct Cli {
     /// The rendered Scene is going to be saved in this file.
     #[structopt(long, short = "o", default_value = "image.png")]
     output: PathBuf,
+    /// Seed for the random number generators. Rendering the same scene with the same seed and
+    /// settings produces the exact same image. If no value is given a random seed is chosen.
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// Edge length in pixels of the square blocks the image is split into. Each block is rendered
+    /// as a whole by one thread.
+    #[structopt(long, default_value = "16")]
+    tile_size: u32,
+    /// Enables adaptive sampling. Pixels stop taking samples once the 95% confidence interval of
+    /// their brightness is smaller than this fraction of the brightness itself (e.g. `0.05`).
+    /// `samples-per-pixel` is still the upper limit.
+    #[structopt(long)]
+    noise_threshold: Option<f64>,
+    /// Strategy to distribute the samples within each pixel, on the lens and for each bounce. One
+    /// of `independent`, `stratified`, `halton` or `sobol`. The latter three spread the samples
+    /// more evenly and produce less noise for the same number of samples.
+    #[structopt(long, default_value = "independent")]
+    sampler: SamplerKind,
+    /// Maps bright colors into the range of 8 bit images. One of `clamp`, `reinhard`,
+    /// `extended-reinhard[:white]`, `aces` or `hable`. Overrides the setting of the scene file.
+    #[structopt(long)]
+    tone_mapper: Option<ToneMapper>,
+    /// Exposure correction in stops. Each stop doubles the brightness of 8 bit output images.
+    /// Overrides the setting of the scene file.
+    #[structopt(long, allow_hyphen_values = true)]
+    exposure: Option<f64>,
+    /// If rendering is interrupted with Ctrl+C, the progress is saved to this file. Defaults to
+    /// the output path with `.checkpoint` appended.
+    #[structopt(long)]
+    checkpoint: Option<PathBuf>,
+    /// Continue an interrupted render from its checkpoint file. Scene and settings must be the same
+    /// as for the interrupted render, except for `samples-per-pixel`, which may be raised to refine
+    /// the image (not with the `stratified` and `sobol` samplers). The seed is taken from the
+    /// checkpoint, unless specified. The checkpoint is deleted once the render is complete.
+    #[structopt(long)]
+    resume: bool,
+    /// How the bounding volume hierarchy is built. Either `sah` (surface area heuristic) or
+    /// `alternate`. Overrides the setting of the scene file.
+    #[structopt(long)]
+    bvh: Option<BvhStrategy>,
+    /// Render a single wavelength per sample instead of RGB colors. Needed to see dispersion of
+    /// dielectrics with a wavelength dependent refractive index. Takes more samples to converge.
+    #[structopt(long)]
+    spectral: bool,
 }
 
 fn main() -> io::Result<()> {
@@ -76,74 +104,163 @@ fn main() -> io::Result<()> {
         image_width,
         input,
         output,
+        seed,
+        tile_size,
+        noise_threshold,
+        sampler,
+        tone_mapper,
+        exposure,
+        checkpoint,
+        resume,
+        bvh,
+        spectral,
     } = Cli::from_args();
 
+    if tile_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Tile size must be at least one pixel.",
+        ));
+    }
+
     let aspect_ratio = image_width as f64 / image_height as f64;
 
-    let mut rng = thread_rng();
+    let checkpoint_path = checkpoint.unwrap_or_else(|| {
+        let mut path = output.clone().into_os_string();
+        path.push(".checkpoint");
+        path.into()
+    });
+    let resumed = if resume {
+        Some(Checkpoint::load(&checkpoint_path)?)
+    } else {
+        None
+    };
+
+    let seed = seed.or_else(|| resumed.as_ref().map(|checkpoint| checkpoint.settings.seed));
+    let seed = seed.unwrap_or_else(|| {
+        let seed = thread_rng().gen();
+        eprintln!(
+            "Using random seed {}. Pass it to --seed to reproduce this image.",
+            seed
+        );
+        seed
+    });
+    let mut rng = StdRng::seed_from_u64(seed);
 
-    let scene = if let Some(path) = input {
+    let mut scene_builder = if let Some(path) = input {
         SceneBuilder::from_path(path)?
     } else {
-        let scene = random_scenes::spheres(&mut rng, aspect_ratio);
+        let scene = random_spheres(&mut rng, aspect_ratio);
         eprintln!("No input scene specified. Saving scene with random spheres to 'scene.json'.");
         scene.to_path("scene.json")?;
         scene
+    };
+    if let Some(tone_mapper) = tone_mapper {
+        scene_builder.display.tone_mapper = tone_mapper;
+    }
+    if let Some(exposure) = exposure {
+        scene_builder.display.exposure = exposure;
     }
-    .build();
+    if let Some(bvh) = bvh {
+        scene_builder.bvh = bvh;
+    }
+    let checkpoint_settings = CheckpointSettings {
+        scene_hash: scene_builder.content_hash(),
+        image_width,
+        image_height,
+        samples_per_pixel,
+        max_depth,
+        seed,
+        sampler,
+        spectral,
+    };
+    let framebuffer = match resumed {
+        Some(checkpoint) => {
+            if !checkpoint.settings.can_resume_with(&checkpoint_settings) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The checkpoint belongs to a different scene, or has been rendered with \
+                    different image dimensions, max depth, seed, sampler or spectral mode. The \
+                    stratified and sobol samplers also require the same samples per pixel.",
+                ));
+            }
+            Framebuffer::from_estimates(image_width, checkpoint.estimates)
+        }
+        None => Framebuffer::new(image_width, image_height),
+    };
+    let build_start = Instant::now();
+    let scene = scene_builder.build()?;
+    eprintln!(
+        "Built scene in {:.2} s.",
+        build_start.elapsed().as_secs_f64()
+    );
 
     eprintln!(
         "Start rendering samples. You can press Ctrl+C to finish rendering the current samples and \
         then produce output immediatly with samples rendered so far."
     );
 
-    let progress_bar = ProgressBar::new(samples_per_pixel as u64);
+    let progress_bar = ProgressBar::new(0);
     progress_bar.set_style(
         ProgressStyle::default_bar()
-            .template("[{elapsed_precise}][{eta}] {wide_bar} samples: {pos}/{len}"),
+            .template("[{elapsed_precise}][{eta}] {wide_bar} tiles: {pos}/{len}"),
     );
 
-    let neutral = || {
-        (
-            vec![Color::new(0., 0., 0.); image_height as usize * image_width as usize],
-            0,
-        )
+    let settings = RenderSettings {
+        image_width,
+        image_height,
+        samples_per_pixel,
+        max_depth,
+        seed,
+        noise_threshold,
+        sampler,
+        spectral,
+        tile_size,
     };
-    // Render samples in parallel and reduce them to one accumulated color vector.
-    let (mut acc_color_buf, num_samples_rendered) = (0..samples_per_pixel)
-        .into_par_iter()
-        .map(|_| {
-            let mut rng = thread_rng();
-            let sample = if running.load(Ordering::SeqCst) {
-                (
-                    scene.render_sample(&mut rng, max_depth, image_height, image_width),
-                    1,
-                )
-            } else {
-                neutral()
-            };
-            progress_bar.inc(1);
-            sample
-        })
-        .reduce(neutral, |(mut acc, acc_num), (sample, weight)| {
-            for index in 0..acc.len() {
-                acc[index] = acc[index] + sample[index];
-            }
-            (acc, acc_num + weight)
-        });
+    render(
+        &scene,
+        &settings,
+        &framebuffer,
+        |Progress {
+             tiles_done,
+             tiles_total,
+         }| {
+            progress_bar.set_length(tiles_total as u64);
+            progress_bar.set_position(tiles_done as u64);
+        },
+        || !running.load(Ordering::SeqCst),
+    );
 
     progress_bar.finish();
 
+    let num_samples_rendered = framebuffer.samples_rendered();
+    let colors = framebuffer.colors();
+
+    if !running.load(Ordering::SeqCst) {
+        let checkpoint = Checkpoint {
+            settings: checkpoint_settings,
+            estimates: framebuffer.into_estimates(),
+        };
+        checkpoint.save(&checkpoint_path)?;
+        eprintln!(
+            "Saved progress to '{}'. Use --resume to continue rendering.",
+            checkpoint_path.display()
+        );
+    } else if resume {
+        // The render is complete, so there is nothing left to resume.
+        fs::remove_file(&checkpoint_path)?;
+    }
+
     if num_samples_rendered == 0 {
         eprintln!("No samples rendered.")
     } else {
-        let samples_f = num_samples_rendered as f64;
-
-        for color in &mut acc_color_buf {
-            *color = *color / samples_f;
+        if noise_threshold.is_some() {
+            eprintln!(
+                "Average samples per pixel: {:.1}",
+                num_samples_rendered as f64 / (image_width * image_height) as f64
+            );
         }
-
-        save_image(&acc_color_buf, image_width, &output)?;
+        save_image(&colors, image_width, &output, &scene_builder.display)?;
 
         eprintln!("Done.");
     }
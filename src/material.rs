@@ -1,23 +1,71 @@
 use crate::vec3::{dot, Color, Vec3};
-use rand::{rngs::ThreadRng, Rng};
+use rand::{Rng, RngCore};
 use std::f64::consts::PI;
 
+mod conductor;
 mod dielectric;
 mod diffuse;
+mod isotropic;
+mod light;
 mod metal;
+mod microfacet;
+mod principled;
+mod refractive_index;
+mod rough_dielectric;
 
+pub use conductor::Conductor;
 pub use dielectric::Dielectric;
-pub use diffuse::{Hemisphere, Lambertian, Simple};
+pub use diffuse::Lambertian;
+pub use isotropic::Isotropic;
+// Alternative diffuse models presented in the tutorial. Not used by any scene, but kept around for
+// comparison.
+#[allow(unused_imports)]
+pub use diffuse::{Hemisphere, Simple};
+pub use light::DiffuseLight;
 pub use metal::Metal;
+pub use principled::{Principled, PrincipledParameters};
+pub use refractive_index::{Dispersion, RefractiveIndex};
+pub use rough_dielectric::RoughDielectric;
 
 pub trait Material {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         incoming: &Vec3,
         normal: &Vec3,
         front_face: bool,
+        _wavelength: Option<f64>,
     ) -> Option<ScatterResult>;
+
+    /// Light emitted by the surface itself. Most materials do not glow.
+    fn emitted(&self) -> Color {
+        Color::ZERO
+    }
+
+    /// Fraction of light, which remains after travelling `length` units through the inside of an
+    /// object made of this material. Only transparent materials absorb light on the way.
+    fn transmittance(&self, _length: f64) -> Color {
+        Color::ONE
+    }
+
+    /// Attenuation for light scattered from `direction` into the opposite of `incoming`, if
+    /// `direction` has been chosen with probability density one. I.e. the BRDF times the cosine
+    /// of the angle between `direction` and `normal`. The second element is the probability
+    /// density of `scatter` picking `direction`.
+    ///
+    /// `None` for materials which scatter only into discrete directions, like mirrors and glass.
+    /// Only materials returning `Some` are ever asked to scatter into directions they did not
+    /// choose themselves.
+    fn evaluate(
+        &self,
+        _incoming: &Vec3,
+        _normal: &Vec3,
+        _front_face: bool,
+        _direction: &Vec3,
+        _wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        None
+    }
 }
 
 impl<M> Material for Box<M>
@@ -26,12 +74,34 @@ where
 {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         incoming: &Vec3,
         normal: &Vec3,
         front_face: bool,
+        wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
-        self.as_ref().scatter(rng, incoming, normal, front_face)
+        self.as_ref()
+            .scatter(rng, incoming, normal, front_face, wavelength)
+    }
+
+    fn emitted(&self) -> Color {
+        self.as_ref().emitted()
+    }
+
+    fn transmittance(&self, length: f64) -> Color {
+        self.as_ref().transmittance(length)
+    }
+
+    fn evaluate(
+        &self,
+        incoming: &Vec3,
+        normal: &Vec3,
+        front_face: bool,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        self.as_ref()
+            .evaluate(incoming, normal, front_face, direction, wavelength)
     }
 }
 
@@ -40,7 +110,7 @@ pub struct ScatterResult {
     pub direction: Vec3,
 }
 
-fn random_in_unit_sphere(rng: &mut impl Rng) -> Vec3 {
+fn random_in_unit_sphere(rng: &mut (impl Rng + ?Sized)) -> Vec3 {
     loop {
         let candidate = Vec3::random(rng, -1., 1.);
         if candidate.length_squared() < 1. {
@@ -50,7 +120,7 @@ fn random_in_unit_sphere(rng: &mut impl Rng) -> Vec3 {
 }
 
 // Lambertion diffusion
-fn random_unit_vector(rng: &mut impl Rng) -> Vec3 {
+fn random_unit_vector(rng: &mut (impl Rng + ?Sized)) -> Vec3 {
     let a = rng.gen_range(0., 2. * PI);
     let z: f64 = rng.gen_range(-1., 1.);
     let r = (1. - z * z).sqrt();
@@ -74,3 +144,85 @@ fn schlick(cosine: f64, refracture_index: f64) -> f64 {
     let r0 = r0 * r0;
     r0 + (1. - r0) * (1. - cosine).powi(5)
 }
+
+/// Exact fraction of unpolarized light reflected at the boundary between two dielectrics. `eta` is
+/// the ratio of the refractive index behind the surface to the one in front of it and `cos_theta`
+/// the cosine of the angle between the incoming light and the normal.
+fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta.clamp(0., 1.);
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        // Total internal reflection
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let r_s = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let r_p = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (r_s * r_s + r_p * r_p)
+}
+
+/// Beer–Lambert law: Fraction of light remaining after travelling `length` through a medium with
+/// the absorption coefficients `absorption` (per unit length, for each color channel).
+fn beer_lambert(absorption: &Color, length: f64) -> Color {
+    Color::new(
+        (-absorption[0] * length).exp(),
+        (-absorption[1] * length).exp(),
+        (-absorption[2] * length).exp(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresnel_dielectric_at_normal_incidence() {
+        for &eta in &[1.5, 1. / 1.5, 2.4] {
+            let expected = ((eta - 1.) / (eta + 1.)) * ((eta - 1.) / (eta + 1.));
+            assert!((fresnel_dielectric(1., eta) - expected).abs() < 1e-12);
+        }
+        assert!(fresnel_dielectric(0.3, 1.) < 1e-12);
+    }
+
+    #[test]
+    fn fresnel_dielectric_is_symmetric() {
+        // Light crossing the boundary in either direction is reflected by the same fraction.
+        let eta = 1.5;
+        for i in 1..=10 {
+            let cos_i = i as f64 / 10.;
+            let cos_t = (1. - (1. - cos_i * cos_i) / (eta * eta)).sqrt();
+            let entering = fresnel_dielectric(cos_i, eta);
+            let leaving = fresnel_dielectric(cos_t, eta.recip());
+            assert!((entering - leaving).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn fresnel_dielectric_total_internal_reflection() {
+        let eta: f64 = 1. / 1.5;
+        let cos_critical = (1. - eta * eta).sqrt();
+        assert_eq!(fresnel_dielectric(cos_critical - 1e-6, eta), 1.);
+        assert!(fresnel_dielectric(cos_critical + 1e-3, eta) < 1.);
+        assert_eq!(fresnel_dielectric(0., eta), 1.);
+    }
+
+    #[test]
+    fn beer_lambert_decays_exponentially() {
+        let absorption = Color::new(0., 0.5, 2.);
+        let transmitted = beer_lambert(&absorption, 3.);
+        assert_eq!(transmitted[0], 1.);
+        assert!((transmitted[1] - (-1.5f64).exp()).abs() < 1e-12);
+        assert!((transmitted[2] - (-6f64).exp()).abs() < 1e-12);
+        // Travelling twice the distance squares the remaining fraction.
+        let twice = beer_lambert(&absorption, 6.);
+        for channel in 0..3 {
+            assert!((twice[channel] - transmitted[channel].powi(2)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn beer_lambert_without_distance_keeps_all_light() {
+        let transmitted = beer_lambert(&Color::new(0.1, 10., 1000.), 0.);
+        assert!(transmitted.iter().all(|&c| c == 1.));
+    }
+}
@@ -0,0 +1,152 @@
+use super::{
+    microfacet::{Ggx, ShadingFrame},
+    reflect, Material, ScatterResult,
+};
+use crate::vec3::{dot, Color, Vec3};
+use rand::RngCore;
+
+/// Metal with microscopic roughness, modelled by the GGX microfacet distribution. Unlike [`Metal`]
+/// it conserves energy and its color is derived from the measured optical constants of real
+/// metals.
+///
+/// [`Metal`]: super::Metal
+pub struct Conductor {
+    /// Real part of the complex index of refraction, for each color channel.
+    eta: Color,
+    /// Imaginary part of the complex index of refraction (extinction coefficient), for each color
+    /// channel.
+    k: Color,
+    distribution: Ggx,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    fn fresnel(&self, cos_theta: f64) -> Color {
+        Color::new(
+            fresnel_conductor(cos_theta, self.eta[0], self.k[0]),
+            fresnel_conductor(cos_theta, self.eta[1], self.k[1]),
+            fresnel_conductor(cos_theta, self.eta[2], self.k[2]),
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        rng: &mut dyn RngCore,
+        incoming: &Vec3,
+        normal: &Vec3,
+        _front_face: bool,
+        _wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        let frame = ShadingFrame::new(normal);
+        let wo = frame.to_local(&-incoming.unit());
+        if wo.z() <= 0. {
+            return None;
+        }
+        if self.distribution.is_smooth() {
+            return Some(ScatterResult {
+                attenuation: self.fresnel(wo.z()),
+                direction: frame.to_world(&Vec3::new(-wo.x(), -wo.y(), wo.z())),
+            });
+        }
+        let m = self.distribution.sample_visible_normal(&wo, rng);
+        let wi = reflect(&-wo, &m);
+        if wi.z() <= 0. {
+            // Reflected into another microfacet. We do not trace multiple bounces on the
+            // microsurface, so this light is lost.
+            return None;
+        }
+        // BRDF times cosine divided by the density of sampling `wi`. Most terms cancel out.
+        let masking = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        Some(ScatterResult {
+            attenuation: self.fresnel(dot(wo, m)) * masking,
+            direction: frame.to_world(&wi),
+        })
+    }
+
+    fn evaluate(
+        &self,
+        incoming: &Vec3,
+        normal: &Vec3,
+        _front_face: bool,
+        direction: &Vec3,
+        _wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        let frame = ShadingFrame::new(normal);
+        let wo = frame.to_local(&-incoming.unit());
+        let wi = frame.to_local(&direction.unit());
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Some((Color::ZERO, 0.));
+        }
+        let m = (wo + wi).unit();
+        let d = self.distribution.d(&m);
+        let value = self.fresnel(dot(wo, m)) * (d * self.distribution.g2(&wo, &wi) / (4. * wo.z()));
+        // Density of the visible normal, transformed from the half vector to the reflected
+        // direction.
+        let pdf = self.distribution.visible_normal_pdf(&wo, &m) / (4. * dot(wo, m));
+        Some((value, pdf))
+    }
+}
+
+/// Fraction of unpolarized light reflected by a conductor with the complex index of refraction
+/// `eta + i k`, for light arriving at an angle with cosine `cos_theta` to the normal.
+fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos_theta = cos_theta.clamp(0., 1.);
+    let cos2 = cos_theta * cos_theta;
+    let sin2 = 1. - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2. * cos_theta * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    0.5 * (r_s + r_p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_incidence_matches_closed_form() {
+        for &(eta, k) in &[(0.2, 3.), (1.5, 0.), (2.9, 3.1)] {
+            let expected = ((eta - 1f64).powi(2) + k * k) / ((eta + 1f64).powi(2) + k * k);
+            assert!((fresnel_conductor(1., eta, k) - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn grazing_light_is_reflected_completely() {
+        assert!((fresnel_conductor(0., 0.2, 3.) - 1.).abs() < 1e-12);
+        assert!((fresnel_conductor(1e-6, 1.5, 0.) - 1.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn without_extinction_equals_dielectric() {
+        for i in 1..=10 {
+            let cos_theta = i as f64 / 10.;
+            let conductor = fresnel_conductor(cos_theta, 1.5, 0.);
+            let dielectric = super::super::fresnel_dielectric(cos_theta, 1.5);
+            assert!((conductor - dielectric).abs() < 1e-12, "at {}", cos_theta);
+        }
+    }
+}
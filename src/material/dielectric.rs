@@ -1,34 +1,41 @@
 // Eta air = 1.0 glass = 1.3 1.7 diamond = 1.4
 
-use super::{reflect, refract, schlick, Material, ScatterResult};
+use super::{beer_lambert, reflect, refract, schlick, Material, RefractiveIndex, ScatterResult};
 use crate::vec3::{dot, Color, Vec3};
-use rand::{rngs::ThreadRng, Rng};
+use rand::{Rng, RngCore};
 
 pub struct Dielectric {
-    refractive_index: f64,
+    refractive_index: RefractiveIndex,
+    /// Absorption coefficient per unit length for each color channel. Zero for clear glass.
+    absorption: Color,
 }
 
 impl Dielectric {
-    pub fn new(refractive_index: f64) -> Self {
-        Self { refractive_index }
+    pub fn new(refractive_index: RefractiveIndex, absorption: Color) -> Self {
+        Self {
+            refractive_index,
+            absorption,
+        }
     }
 }
 
 impl Material for Dielectric {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         incoming: &Vec3,
         normal: &Vec3,
         front_face: bool,
+        wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
+        let refractive_index = self.refractive_index.at(wavelength);
         let unit_incoming = incoming.unit();
         let cos_theta = dot(-unit_incoming, *normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let etai_over_etat = if front_face {
-            self.refractive_index.recip()
+            refractive_index.recip()
         } else {
-            self.refractive_index
+            refractive_index
         };
         let must_reflect = etai_over_etat * sin_theta > 1.0;
         let direction = if must_reflect || rng.gen_bool(schlick(cos_theta, etai_over_etat)) {
@@ -44,4 +51,8 @@ impl Material for Dielectric {
             attenuation: Color::new(1., 1., 1.),
         })
     }
+
+    fn transmittance(&self, length: f64) -> Color {
+        beer_lambert(&self.absorption, length)
+    }
 }
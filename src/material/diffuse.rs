@@ -1,6 +1,7 @@
 use super::{random_in_unit_sphere, random_unit_vector, Material, ScatterResult};
 use crate::vec3::{dot, Color, Vec3};
-use rand::rngs::ThreadRng;
+use rand::RngCore;
+use std::f64::consts::PI;
 
 pub struct Lambertian {
     albedo: Color,
@@ -15,18 +16,38 @@ impl Lambertian {
 impl Material for Lambertian {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         _incoming: &Vec3,
         normal: &Vec3,
         _front_face: bool,
+        _wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
         Some(ScatterResult {
             attenuation: self.albedo,
             direction: *normal + random_unit_vector(rng),
         })
     }
+
+    fn evaluate(
+        &self,
+        _incoming: &Vec3,
+        normal: &Vec3,
+        _front_face: bool,
+        direction: &Vec3,
+        _wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        // Offsetting the normal by a random unit vector results in directions distributed
+        // proportional to the cosine.
+        let cosine = dot(*normal, direction.unit());
+        if cosine > 0. {
+            Some((self.albedo * (cosine / PI), cosine / PI))
+        } else {
+            Some((Color::ZERO, 0.))
+        }
+    }
 }
 
+#[allow(dead_code)]
 pub struct Simple {
     pub albedo: Color,
 }
@@ -34,10 +55,11 @@ pub struct Simple {
 impl Material for Simple {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         _incoming: &Vec3,
         normal: &Vec3,
         _front_face: bool,
+        _wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
         Some(ScatterResult {
             attenuation: self.albedo,
@@ -46,6 +68,7 @@ impl Material for Simple {
     }
 }
 
+#[allow(dead_code)]
 pub struct Hemisphere {
     pub albedo: Color,
 }
@@ -56,10 +79,11 @@ pub struct Hemisphere {
 impl Material for Hemisphere {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         _incoming: &Vec3,
         normal: &Vec3,
         _front_face: bool,
+        _wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
         let in_unit_sphere = random_in_unit_sphere(rng);
         let direction = if dot(in_unit_sphere, *normal) > 0.0 {
@@ -0,0 +1,45 @@
+use super::{random_unit_vector, Material, ScatterResult};
+use crate::vec3::{Color, Vec3};
+use rand::RngCore;
+use std::f64::consts::PI;
+
+/// Phase function of participating media, like fog or smoke. Scatters uniformly into all
+/// directions, regardless of the incoming ray.
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        rng: &mut dyn RngCore,
+        _incoming: &Vec3,
+        _normal: &Vec3,
+        _front_face: bool,
+        _wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        Some(ScatterResult {
+            attenuation: self.albedo,
+            direction: random_unit_vector(rng),
+        })
+    }
+
+    fn evaluate(
+        &self,
+        _incoming: &Vec3,
+        _normal: &Vec3,
+        _front_face: bool,
+        _direction: &Vec3,
+        _wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        // There is no surface, hence no cosine term.
+        let density = 1. / (4. * PI);
+        Some((self.albedo * density, density))
+    }
+}
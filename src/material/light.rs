@@ -0,0 +1,33 @@
+use super::{Material, ScatterResult};
+use crate::vec3::{Color, Vec3};
+use rand::RngCore;
+
+/// A glowing surface, which emits light in all directions and does not reflect any.
+pub struct DiffuseLight {
+    /// Color and intensity of the emitted light. Components may (and usually should) be larger than
+    /// one in order to illuminate a scene.
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _rng: &mut dyn RngCore,
+        _incoming: &Vec3,
+        _normal: &Vec3,
+        _front_face: bool,
+        _wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        None
+    }
+
+    fn emitted(&self) -> Color {
+        self.emit
+    }
+}
@@ -1,6 +1,6 @@
 use super::{random_in_unit_sphere, reflect, Material, ScatterResult};
 use crate::vec3::{dot, Color, Vec3};
-use rand::rngs::ThreadRng;
+use rand::RngCore;
 
 pub struct Metal {
     albedo: Color,
@@ -17,10 +17,11 @@ impl Metal {
 impl Material for Metal {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         incoming: &Vec3,
         normal: &Vec3,
         _front_face: bool,
+        _wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
         let reflected = reflect(incoming, normal);
         if dot(reflected, *normal) > 0. {
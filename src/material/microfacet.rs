@@ -0,0 +1,200 @@
+use crate::vec3::{cross, dot, orthonormal_basis, Vec3};
+use rand::{Rng, RngCore};
+use std::f64::consts::PI;
+
+/// Surfaces with an `alpha` below this are treated as perfectly smooth. The distribution becomes
+/// too narrow to be sampled or evaluated reliably.
+const SMOOTH_ALPHA: f64 = 1e-3;
+
+/// Local coordinate system of a surface point, with the normal as z axis. Microfacet models are
+/// much simpler to express in it.
+pub struct ShadingFrame {
+    tangent: Vec3,
+    bitangent: Vec3,
+    normal: Vec3,
+}
+
+impl ShadingFrame {
+    pub fn new(normal: &Vec3) -> Self {
+        let normal = normal.unit();
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        Self {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            dot(*v, self.tangent),
+            dot(*v, self.bitangent),
+            dot(*v, self.normal),
+        )
+    }
+
+    pub fn to_world(&self, v: &Vec3) -> Vec3 {
+        self.tangent * v.x() + self.bitangent * v.y() + self.normal * v.z()
+    }
+}
+
+/// Trowbridge-Reitz (GGX) distribution of microfacet normals, together with the Smith model for
+/// masking and shadowing. All directions are in the local coordinates of a [`ShadingFrame`] and of
+/// unit length.
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// `roughness` from 0 (mirror) to 1 (very rough). It is squared to obtain `alpha`, which makes
+    /// the perceived roughness change more evenly.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Self {
+            alpha: roughness * roughness,
+        }
+    }
+
+    /// `true` if the surface should be treated as a perfect mirror (or perfectly smooth glass).
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < SMOOTH_ALPHA
+    }
+
+    /// Density of microfacets with normal `m`, per unit area of the macro surface.
+    pub fn d(&self, m: &Vec3) -> f64 {
+        if m.z() <= 0. {
+            return 0.;
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let denominator = m.z() * m.z() * (alpha2 - 1.) + 1.;
+        alpha2 / (PI * denominator * denominator)
+    }
+
+    /// Auxiliary function of the Smith masking function.
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0. {
+            return f64::INFINITY;
+        }
+        let tan2 = (1. - cos2).max(0.) / cos2;
+        ((1. + self.alpha * self.alpha * tan2).sqrt() - 1.) / 2.
+    }
+
+    /// Fraction of microfacets visible from direction `w`.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Fraction of microfacets visible from both `wo` and `wi` (height correlated).
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Microfacet normal sampled proportional to its visible area, as seen from `wo` (which must
+    /// be above the surface). See Heitz 2018, "Sampling the GGX Distribution of Visible Normals".
+    pub fn sample_visible_normal(&self, wo: &Vec3, rng: &mut dyn RngCore) -> Vec3 {
+        // Stretch the view direction, so the distribution becomes a hemisphere.
+        let vh = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).unit();
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0. {
+            Vec3::new(-vh.y(), vh.x(), 0.) / length_squared.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = cross(&vh, &t1);
+        // Uniform point on the projection of the hemisphere, seen from `vh`.
+        let r = rng.gen_range(0., 1f64).sqrt();
+        let phi = 2. * PI * rng.gen_range(0., 1.);
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+        // Undo the stretch.
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(0.)).unit()
+    }
+
+    /// Probability density of [`Self::sample_visible_normal`] picking `m`.
+    pub fn visible_normal_pdf(&self, wo: &Vec3, m: &Vec3) -> f64 {
+        self.g1(wo) * dot(*wo, *m).max(0.) * self.d(m) / wo.z()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Integral of `f` over the upper hemisphere, using the midpoint rule in `cos_theta` and `phi`.
+    fn integrate_hemisphere(f: impl Fn(&Vec3) -> f64) -> f64 {
+        let (steps_theta, steps_phi) = (2000, 200);
+        let mut sum = 0.;
+        for i in 0..steps_theta {
+            let cos_theta = (i as f64 + 0.5) / steps_theta as f64;
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+            for j in 0..steps_phi {
+                let phi = 2. * PI * (j as f64 + 0.5) / steps_phi as f64;
+                sum += f(&Vec3::new(
+                    sin_theta * phi.cos(),
+                    sin_theta * phi.sin(),
+                    cos_theta,
+                ));
+            }
+        }
+        sum * 2. * PI / (steps_theta * steps_phi) as f64
+    }
+
+    fn view_directions() -> Vec<Vec3> {
+        vec![
+            Vec3::new(0., 0., 1.),
+            Vec3::new(0.5, 0., 0.8).unit(),
+            Vec3::new(-0.6, 0.7, 0.2).unit(),
+        ]
+    }
+
+    #[test]
+    fn projected_microfacet_area_equals_macro_surface() {
+        for &roughness in &[0.3, 0.6, 1.] {
+            let ggx = Ggx::from_roughness(roughness);
+            let area = integrate_hemisphere(|m| ggx.d(m) * m.z());
+            assert!((area - 1.).abs() < 5e-3);
+        }
+    }
+
+    #[test]
+    fn visible_normal_pdf_is_normalized() {
+        for &roughness in &[0.3, 0.6, 1.] {
+            let ggx = Ggx::from_roughness(roughness);
+            for wo in view_directions() {
+                let total = integrate_hemisphere(|m| ggx.visible_normal_pdf(&wo, m));
+                assert!((total - 1.).abs() < 5e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_normals_follow_their_pdf() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let ggx = Ggx::from_roughness(0.6);
+        for wo in view_directions() {
+            let samples = 100_000;
+            let mean = (0..samples)
+                .map(|_| ggx.sample_visible_normal(&wo, &mut rng))
+                .fold(Vec3::ZERO, |sum, m| sum + m)
+                / samples as f64;
+            for axis in 0..3 {
+                let expected = integrate_hemisphere(|m| m[axis] * ggx.visible_normal_pdf(&wo, m));
+                assert!((mean[axis] - expected).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn masking_is_a_fraction() {
+        let ggx = Ggx::from_roughness(0.7);
+        let wi = Vec3::new(0.3, -0.2, 0.9).unit();
+        for wo in view_directions() {
+            assert!(ggx.g1(&wo) > 0. && ggx.g1(&wo) <= 1.);
+            assert!(ggx.g2(&wo, &wi) <= ggx.g1(&wo).min(ggx.g1(&wi)));
+        }
+        assert_eq!(ggx.g1(&Vec3::new(0., 0., 1.)), 1.);
+    }
+}
@@ -0,0 +1,451 @@
+use super::{
+    fresnel_dielectric,
+    microfacet::{Ggx, ShadingFrame},
+    random_unit_vector, reflect, refract, Material, ScatterResult,
+};
+use crate::vec3::{dot, luminance, Color, Vec3};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Microfacet models become numerically unstable for perfectly smooth surfaces. Unlike the
+/// conductor, this material can not treat them as mirrors, since it mixes specular with diffuse
+/// scattering.
+const MIN_ROUGHNESS: f64 = 0.04;
+
+/// Parameters of the principled material, modelled after the Disney principled BRDF. All of them
+/// are within [0, 1]. Parameters missing in scene files take their default values.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct PrincipledParameters {
+    /// Albedo of the diffuse part, color of metals and tint of transmitted light.
+    pub base_color: Color,
+    /// Blends from a dielectric (0) to a metallic (1) surface.
+    pub metallic: f64,
+    /// Roughness of the specular reflection and transmission, from 0 (polished) to 1 (matte).
+    pub roughness: f64,
+    /// Reflectance of dielectrics at normal incidence. 0.5 corresponds to 4%, i.e. a refractive
+    /// index of 1.5, which is typical for most materials.
+    pub specular: f64,
+    /// Soft white highlight at grazing angles, like the one of cloth.
+    pub sheen: f64,
+    /// Strength of a second, white specular layer on top, like the varnish of a car.
+    pub clearcoat: f64,
+    /// Roughness of the clearcoat layer. Like `roughness`, values below 0.04 are treated as 0.04.
+    pub clearcoat_roughness: f64,
+    /// Blends the diffuse part of dielectrics into transmission through the surface (glass).
+    pub transmission: f64,
+}
+
+impl PrincipledParameters {
+    /// `false` if any parameter is outside of [0, 1].
+    pub fn is_valid(&self) -> bool {
+        let unit = |value: f64| (0. ..=1.).contains(&value);
+        self.base_color.iter().all(|&component| unit(component))
+            && [
+                self.metallic,
+                self.roughness,
+                self.specular,
+                self.sheen,
+                self.clearcoat,
+                self.clearcoat_roughness,
+                self.transmission,
+            ]
+            .iter()
+            .all(|&value| unit(value))
+    }
+}
+
+impl Default for PrincipledParameters {
+    fn default() -> Self {
+        Self {
+            base_color: Color::new(0.8, 0.8, 0.8),
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            sheen: 0.,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.05,
+            transmission: 0.,
+        }
+    }
+}
+
+/// One material combining diffuse, metallic, glossy and transparent surfaces, controlled by a few
+/// intuitive parameters. Close to the principled shaders of most 3D modelling tools, so scenes
+/// exported from them can be mapped onto it.
+pub struct Principled {
+    parameters: PrincipledParameters,
+    refractive_index: f64,
+    specular: Ggx,
+    clearcoat: Ggx,
+}
+
+/// Probabilities of sampling each of the lobes.
+struct LobeWeights {
+    diffuse: f64,
+    specular: f64,
+    clearcoat: f64,
+    transmission: f64,
+}
+
+impl Principled {
+    pub fn new(parameters: PrincipledParameters) -> Self {
+        // Invert the Fresnel equation at normal incidence. A refractive index of one would leave
+        // transmission without a defined half vector.
+        let f0 = (0.08 * parameters.specular).sqrt().clamp(0.01, 0.99);
+        Self {
+            parameters,
+            refractive_index: (1. + f0) / (1. - f0),
+            specular: Ggx::from_roughness(parameters.roughness.max(MIN_ROUGHNESS)),
+            clearcoat: Ggx::from_roughness(parameters.clearcoat_roughness.max(MIN_ROUGHNESS)),
+        }
+    }
+
+    fn eta(&self, front_face: bool) -> f64 {
+        if front_face {
+            self.refractive_index
+        } else {
+            self.refractive_index.recip()
+        }
+    }
+
+    /// Fresnel reflectance of the specular lobe. Blends between the dielectric and the tinted
+    /// metallic reflection.
+    fn specular_fresnel(&self, cos_theta: f64, eta: f64) -> Color {
+        let PrincipledParameters {
+            base_color,
+            metallic,
+            ..
+        } = self.parameters;
+        schlick(&base_color, cos_theta) * metallic
+            + Color::ONE * ((1. - metallic) * fresnel_dielectric(cos_theta, eta))
+    }
+
+    /// Fractions of light seen from `wo`, which make it through the layers on top of the diffuse
+    /// base and through the clearcoat. Light reflected by a layer can not be scattered by the
+    /// layers below as well, otherwise the surface would reflect more light than it receives.
+    fn layer_transmittance(&self, wo: &Vec3, eta: f64) -> (f64, f64) {
+        let below_clearcoat = 1. - self.parameters.clearcoat * clearcoat_fresnel(wo.z());
+        let below_specular = below_clearcoat * (1. - fresnel_dielectric(wo.z(), eta));
+        (below_specular, below_clearcoat)
+    }
+
+    /// Chooses the lobes roughly proportional to the light they scatter into, as seen from `wo`.
+    fn lobe_weights(&self, wo: &Vec3, eta: f64) -> Option<LobeWeights> {
+        let PrincipledParameters {
+            metallic,
+            clearcoat,
+            transmission,
+            ..
+        } = self.parameters;
+        let dielectric = 1. - metallic;
+        let (below_specular, below_clearcoat) = self.layer_transmittance(wo, eta);
+        let diffuse = dielectric * (1. - transmission) * below_specular;
+        let specular = luminance(&self.specular_fresnel(wo.z(), eta)) * below_clearcoat;
+        let clearcoat = 0.25 * clearcoat * clearcoat_fresnel(wo.z());
+        let transmission =
+            dielectric * transmission * (1. - fresnel_dielectric(wo.z(), eta)) * below_clearcoat;
+        let total = diffuse + specular + clearcoat + transmission;
+        if total <= 0. {
+            return None;
+        }
+        Some(LobeWeights {
+            diffuse: diffuse / total,
+            specular: specular / total,
+            clearcoat: clearcoat / total,
+            transmission: transmission / total,
+        })
+    }
+
+    /// BSDF times cosine and the combined density of all lobes sampling `wi`. Both directions in
+    /// local coordinates.
+    fn evaluate_local(
+        &self,
+        wo: &Vec3,
+        wi: &Vec3,
+        eta: f64,
+        weights: &LobeWeights,
+    ) -> (Color, f64) {
+        let PrincipledParameters {
+            base_color,
+            metallic,
+            sheen,
+            clearcoat,
+            transmission,
+            ..
+        } = self.parameters;
+        let dielectric = 1. - metallic;
+        let (below_specular, below_clearcoat) = self.layer_transmittance(wo, eta);
+
+        if wi.z() > 0. {
+            let h = (*wo + *wi).unit();
+            let cos_d = dot(*wi, h);
+
+            let diffuse_weight = dielectric * (1. - transmission) * below_specular;
+            let diffuse = base_color * (diffuse_weight / PI)
+                + Color::ONE * (diffuse_weight * sheen * (1. - cos_d).max(0.).powi(5));
+
+            let specular_d = self.specular.d(&h);
+            let specular = self.specular_fresnel(dot(*wo, h), eta)
+                * (specular_d * self.specular.g2(wo, wi) * below_clearcoat
+                    / (4. * wo.z() * wi.z()));
+
+            let clearcoat_d = self.clearcoat.d(&h);
+            let clearcoat = 0.25
+                * clearcoat
+                * clearcoat_fresnel(dot(*wo, h))
+                * clearcoat_d
+                * self.clearcoat.g2(wo, wi)
+                / (4. * wo.z() * wi.z());
+
+            let value = (diffuse + specular + Color::ONE * clearcoat) * wi.z();
+            let pdf = weights.diffuse * wi.z() / PI
+                + weights.specular * self.specular.visible_normal_pdf(wo, &h) / (4. * dot(*wo, h))
+                + weights.clearcoat * self.clearcoat.visible_normal_pdf(wo, &h)
+                    / (4. * dot(*wo, h));
+            return (value, pdf);
+        }
+
+        // Transmission, see `RoughDielectric`.
+        let mut h = (*wo + *wi * eta).unit();
+        if h.z() < 0. {
+            h = -h;
+        }
+        let (cos_o, cos_i) = (dot(*wo, h), dot(*wi, h));
+        if cos_o <= 0. || cos_i >= 0. || transmission == 0. {
+            return (Color::ZERO, 0.);
+        }
+        let fresnel = fresnel_dielectric(cos_o, eta);
+        let jacobian = eta * eta * -cos_i / (cos_o + eta * cos_i).powi(2);
+        let d = self.specular.d(&h);
+        let value = base_color
+            * (dielectric
+                * transmission
+                * below_clearcoat
+                * (1. - fresnel)
+                * d
+                * self.specular.g2(wo, wi)
+                * cos_o
+                * jacobian
+                / wo.z());
+        let pdf = weights.transmission * self.specular.visible_normal_pdf(wo, &h) * jacobian;
+        (value, pdf)
+    }
+}
+
+/// Reflectance of the clearcoat layer at normal incidence, i.e. of a refractive index of 1.5.
+const CLEARCOAT_F0: f64 = 0.04;
+
+/// Reflects `wo` on a microfacet sampled from `distribution`. `None` if the reflection points
+/// below the surface.
+fn sample_reflection(distribution: &Ggx, wo: &Vec3, rng: &mut dyn RngCore) -> Option<Vec3> {
+    let wi = reflect(&-*wo, &distribution.sample_visible_normal(wo, rng));
+    if wi.z() > 0. {
+        Some(wi)
+    } else {
+        None
+    }
+}
+
+fn clearcoat_fresnel(cos_theta: f64) -> f64 {
+    schlick(&(Color::ONE * CLEARCOAT_F0), cos_theta)[0]
+}
+
+/// Schlick's approximation of the Fresnel term for reflectance `f0` at normal incidence.
+fn schlick(f0: &Color, cos_theta: f64) -> Color {
+    let falloff = (1. - cos_theta.clamp(0., 1.)).powi(5);
+    *f0 + (Color::ONE - *f0) * falloff
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        rng: &mut dyn RngCore,
+        incoming: &Vec3,
+        normal: &Vec3,
+        front_face: bool,
+        _wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        let eta = self.eta(front_face);
+        let frame = ShadingFrame::new(normal);
+        let wo = frame.to_local(&-incoming.unit());
+        if wo.z() <= 0. {
+            return None;
+        }
+        let weights = self.lobe_weights(&wo, eta)?;
+
+        // Directions ending up on the wrong side of the surface would be attributed to the wrong
+        // lobes by `evaluate_local`. That light is lost, as for `RoughDielectric`.
+        let lobe = rng.gen_range(0., 1.);
+        let wi = if lobe < weights.diffuse {
+            // Offsetting the normal by a random unit vector results in a cosine distribution.
+            let direction = Vec3::new(0., 0., 1.) + random_unit_vector(rng);
+            if direction.length_squared() < 1e-12 {
+                Vec3::new(0., 0., 1.)
+            } else {
+                direction.unit()
+            }
+        } else if lobe < weights.diffuse + weights.specular {
+            sample_reflection(&self.specular, &wo, rng)?
+        } else if lobe < weights.diffuse + weights.specular + weights.transmission {
+            let m = self.specular.sample_visible_normal(&wo, rng);
+            if fresnel_dielectric(dot(wo, m), eta) >= 1. {
+                // Total internal reflection. The specular lobe accounts for it.
+                return None;
+            }
+            let wi = refract(&-wo, &m, eta.recip());
+            if wi.z() >= 0. {
+                return None;
+            }
+            wi
+        } else {
+            sample_reflection(&self.clearcoat, &wo, rng)?
+        };
+
+        let (value, pdf) = self.evaluate_local(&wo, &wi, eta, &weights);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(ScatterResult {
+            attenuation: value / pdf,
+            direction: frame.to_world(&wi),
+        })
+    }
+
+    fn evaluate(
+        &self,
+        incoming: &Vec3,
+        normal: &Vec3,
+        front_face: bool,
+        direction: &Vec3,
+        _wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        let eta = self.eta(front_face);
+        let frame = ShadingFrame::new(normal);
+        let wo = frame.to_local(&-incoming.unit());
+        let wi = frame.to_local(&direction.unit());
+        if wo.z() <= 0. || wi.z() == 0. {
+            return Some((Color::ZERO, 0.));
+        }
+        let weights = match self.lobe_weights(&wo, eta) {
+            Some(weights) => weights,
+            None => return Some((Color::ZERO, 0.)),
+        };
+        Some(self.evaluate_local(&wo, &wi, eta, &weights))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn materials() -> Vec<Principled> {
+        let defaults = PrincipledParameters::default();
+        vec![
+            // Opaque dielectric with sheen.
+            PrincipledParameters {
+                base_color: Color::new(0.8, 0.3, 0.2),
+                sheen: 0.5,
+                ..defaults
+            },
+            PrincipledParameters {
+                base_color: Color::new(0.9, 0.6, 0.3),
+                metallic: 1.,
+                roughness: 0.4,
+                ..defaults
+            },
+            PrincipledParameters {
+                roughness: 0.6,
+                clearcoat: 1.,
+                clearcoat_roughness: 0.3,
+                ..defaults
+            },
+            PrincipledParameters {
+                base_color: Color::new(0.9, 0.9, 1.),
+                roughness: 0.4,
+                transmission: 1.,
+                clearcoat: 0.5,
+                clearcoat_roughness: 0.3,
+                ..defaults
+            },
+        ]
+        .into_iter()
+        .map(Principled::new)
+        .collect()
+    }
+
+    /// Incoming directions hitting the front and the back face of a surface with normal `+z`.
+    fn incoming_directions() -> Vec<(Vec3, bool)> {
+        vec![
+            (Vec3::new(0., 0., -1.), true),
+            (Vec3::new(0.6, 0., -0.8), true),
+            (Vec3::new(-0.3, 0.2, -0.9).unit(), false),
+            (Vec3::new(0.7, 0.1, -0.5).unit(), false),
+        ]
+    }
+
+    #[test]
+    fn attenuation_matches_evaluation() {
+        let mut rng = StdRng::seed_from_u64(21);
+        let normal = Vec3::new(0., 0., 1.);
+        for material in materials() {
+            for (incoming, front_face) in incoming_directions() {
+                for _ in 0..1000 {
+                    let scattered =
+                        match material.scatter(&mut rng, &incoming, &normal, front_face, None) {
+                            Some(scattered) => scattered,
+                            None => continue,
+                        };
+                    let (value, pdf) = material
+                        .evaluate(&incoming, &normal, front_face, &scattered.direction, None)
+                        .unwrap();
+                    let expected = value / pdf;
+                    assert!((scattered.attenuation - expected).length() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_is_normalized_over_the_sphere() {
+        let mut rng = StdRng::seed_from_u64(22);
+        let normal = Vec3::new(0., 0., 1.);
+        let (steps_theta, steps_phi) = (1000, 100);
+        for material in materials() {
+            for (incoming, front_face) in incoming_directions() {
+                // Midpoint rule in spherical coordinates, which resolves the lobes around the
+                // normal better than steps of equal solid angle.
+                let mut total = 0.;
+                for i in 0..steps_theta {
+                    let theta = PI * (i as f64 + 0.5) / steps_theta as f64;
+                    for j in 0..steps_phi {
+                        let phi = 2. * PI * (j as f64 + 0.5) / steps_phi as f64;
+                        let direction = Vec3::new(
+                            theta.sin() * phi.cos(),
+                            theta.sin() * phi.sin(),
+                            theta.cos(),
+                        );
+                        let (_, pdf) = material
+                            .evaluate(&incoming, &normal, front_face, &direction, None)
+                            .unwrap();
+                        total += pdf * theta.sin();
+                    }
+                }
+                total *= 2. * PI * PI / (steps_theta * steps_phi) as f64;
+                // Samples ending up on the wrong side of the surface are lost.
+                let samples = 50_000;
+                let scattered = (0..samples)
+                    .filter(|_| {
+                        material
+                            .scatter(&mut rng, &incoming, &normal, front_face, None)
+                            .is_some()
+                    })
+                    .count();
+                let expected = scattered as f64 / samples as f64;
+                assert!((total - expected).abs() < 0.01);
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Index of refraction of a dielectric. In scene files either a plain number, or one of the
+/// dispersion formulas.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(untagged)]
+pub enum RefractiveIndex {
+    /// The same for all wavelengths.
+    Constant(f64),
+    /// Depends on the wavelength, which splits white light into its colors (e.g. in a prism). Only
+    /// visible in spectral rendering.
+    Dispersive(Dispersion),
+}
+
+/// Formulas for the refractive index `n` depending on the wavelength `λ` in micrometers.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Dispersion {
+    /// Cauchy's equation `n = a + b / λ²`. E.g. `a = 1.5046` and `b = 0.0042` for BK7 glass.
+    Cauchy { a: f64, b: f64 },
+    /// Sellmeier equation `n² = 1 + Σ b[i] λ² / (λ² - c[i])`. More accurate than Cauchy's
+    /// equation, and tabulated for many materials. E.g. `b = [0.3306, 4.3356, 0]` and
+    /// `c = [0.030625, 0.011236, 0]` for diamond.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl RefractiveIndex {
+    /// Wavelength in nanometers at which dispersive materials are evaluated when rendering in RGB.
+    /// The sodium d-line, at which refractive indices are usually specified.
+    const RGB_WAVELENGTH: f64 = 587.6;
+
+    /// Refractive index for light of `wavelength` in nanometers, or for RGB rendering if `None`.
+    pub fn at(&self, wavelength: Option<f64>) -> f64 {
+        let dispersion = match self {
+            RefractiveIndex::Constant(index) => return *index,
+            RefractiveIndex::Dispersive(dispersion) => dispersion,
+        };
+        let micrometers = wavelength.unwrap_or(Self::RGB_WAVELENGTH) / 1000.;
+        let lambda2 = micrometers * micrometers;
+        match dispersion {
+            Dispersion::Cauchy { a, b } => a + b / lambda2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = b
+                    .iter()
+                    .zip(c)
+                    .map(|(b, c)| b * lambda2 / (lambda2 - c))
+                    .sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+}
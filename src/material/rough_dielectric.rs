@@ -0,0 +1,217 @@
+use super::{
+    beer_lambert, fresnel_dielectric,
+    microfacet::{Ggx, ShadingFrame},
+    reflect, refract, Material, RefractiveIndex, ScatterResult,
+};
+use crate::vec3::{dot, Color, Vec3};
+use rand::{Rng, RngCore};
+
+/// Glass or other transparent material with a microscopically rough surface (e.g. frosted glass),
+/// modelled by the GGX microfacet distribution. Light is both reflected and transmitted into a
+/// range of directions, depending on the roughness.
+///
+/// Like [`super::Dielectric`], radiance is not scaled by the squared ratio of the refractive
+/// indices when crossing the surface. The factor cancels out for paths entering and leaving an
+/// object anyway.
+pub struct RoughDielectric {
+    refractive_index: RefractiveIndex,
+    /// Absorption coefficient per unit length for each color channel. Zero for clear glass.
+    absorption: Color,
+    distribution: Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(refractive_index: RefractiveIndex, absorption: Color, roughness: f64) -> Self {
+        Self {
+            refractive_index,
+            absorption,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    /// Ratio of the refractive index behind the surface to the one in front of it.
+    fn eta(&self, front_face: bool, wavelength: Option<f64>) -> f64 {
+        let refractive_index = self.refractive_index.at(wavelength);
+        if front_face {
+            refractive_index
+        } else {
+            refractive_index.recip()
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        rng: &mut dyn RngCore,
+        incoming: &Vec3,
+        normal: &Vec3,
+        front_face: bool,
+        wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        let eta = self.eta(front_face, wavelength);
+        let frame = ShadingFrame::new(normal);
+        let wo = frame.to_local(&-incoming.unit());
+        if wo.z() <= 0. {
+            return None;
+        }
+        let m = if self.distribution.is_smooth() {
+            Vec3::new(0., 0., 1.)
+        } else {
+            self.distribution.sample_visible_normal(&wo, rng)
+        };
+        // Choosing between reflection and transmission proportional to the Fresnel term cancels
+        // it out of the attenuation.
+        let fresnel = fresnel_dielectric(dot(wo, m), eta);
+        let wi = if rng.gen_bool(fresnel.clamp(0., 1.)) {
+            let wi = reflect(&-wo, &m);
+            if wi.z() <= 0. {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract(&-wo, &m, eta.recip());
+            if wi.z() >= 0. {
+                return None;
+            }
+            wi
+        };
+        let attenuation = if self.distribution.is_smooth() {
+            Color::ONE
+        } else {
+            Color::ONE * (self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo))
+        };
+        Some(ScatterResult {
+            attenuation,
+            direction: frame.to_world(&wi),
+        })
+    }
+
+    fn transmittance(&self, length: f64) -> Color {
+        beer_lambert(&self.absorption, length)
+    }
+
+    fn evaluate(
+        &self,
+        incoming: &Vec3,
+        normal: &Vec3,
+        front_face: bool,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        if self.distribution.is_smooth() {
+            return None;
+        }
+        let eta = self.eta(front_face, wavelength);
+        let frame = ShadingFrame::new(normal);
+        let wo = frame.to_local(&-incoming.unit());
+        let wi = frame.to_local(&direction.unit());
+        if wo.z() <= 0. || wi.z() == 0. {
+            return Some((Color::ZERO, 0.));
+        }
+        let g2 = self.distribution.g2(&wo, &wi);
+
+        if wi.z() > 0. {
+            let m = (wo + wi).unit();
+            let fresnel = fresnel_dielectric(dot(wo, m), eta);
+            let d = self.distribution.d(&m);
+            let value = fresnel * d * g2 / (4. * wo.z());
+            let pdf = fresnel * self.distribution.visible_normal_pdf(&wo, &m) / (4. * dot(wo, m));
+            return Some((Color::ONE * value, pdf));
+        }
+
+        // Generalized half vector of refraction, oriented like the normal.
+        let mut m = (wo + wi * eta).unit();
+        if m.z() < 0. {
+            m = -m;
+        }
+        let (cos_o, cos_i) = (dot(wo, m), dot(wi, m));
+        // The microfacet must face `wo` and have `wi` behind it.
+        if cos_o <= 0. || cos_i >= 0. {
+            return Some((Color::ZERO, 0.));
+        }
+        let fresnel = fresnel_dielectric(cos_o, eta);
+        let denominator = (cos_o + eta * cos_i).powi(2);
+        let d = self.distribution.d(&m);
+        // Change of variables from the microfacet normal to the refracted direction.
+        let jacobian = eta * eta * -cos_i / denominator;
+        let value = (1. - fresnel) * d * g2 * cos_o * jacobian / wo.z();
+        let pdf = (1. - fresnel) * self.distribution.visible_normal_pdf(&wo, &m) * jacobian;
+        Some((Color::ONE * value, pdf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::f64::consts::PI;
+
+    fn glass() -> RoughDielectric {
+        RoughDielectric::new(RefractiveIndex::Constant(1.5), Color::ZERO, 0.5)
+    }
+
+    fn incoming_directions() -> Vec<(Vec3, bool)> {
+        vec![
+            (Vec3::new(0., 0., -1.), true),
+            (Vec3::new(0.6, 0., -0.8), true),
+            (Vec3::new(-0.3, 0.2, -0.9).unit(), false),
+        ]
+    }
+
+    #[test]
+    fn attenuation_matches_evaluation() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let material = glass();
+        let normal = Vec3::new(0., 0., 1.);
+        for (incoming, front_face) in incoming_directions() {
+            for _ in 0..1000 {
+                let scattered =
+                    match material.scatter(&mut rng, &incoming, &normal, front_face, None) {
+                        Some(scattered) => scattered,
+                        None => continue,
+                    };
+                let (value, pdf) = material
+                    .evaluate(&incoming, &normal, front_face, &scattered.direction, None)
+                    .unwrap();
+                let expected = value / pdf;
+                assert!((scattered.attenuation - expected).length() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn pdf_is_normalized_over_the_sphere() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let material = glass();
+        let normal = Vec3::new(0., 0., 1.);
+        let (steps_z, steps_phi) = (2000, 200);
+        for (incoming, front_face) in incoming_directions() {
+            let mut total = 0.;
+            for i in 0..steps_z {
+                let z = -1. + 2. * (i as f64 + 0.5) / steps_z as f64;
+                let r = (1. - z * z).sqrt();
+                for j in 0..steps_phi {
+                    let phi = 2. * PI * (j as f64 + 0.5) / steps_phi as f64;
+                    let direction = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                    let (_, pdf) = material
+                        .evaluate(&incoming, &normal, front_face, &direction, None)
+                        .unwrap();
+                    total += pdf;
+                }
+            }
+            total *= 4. * PI / (steps_z * steps_phi) as f64;
+            // Samples reflected into or refracted out of the surface are lost.
+            let samples = 100_000;
+            let scattered = (0..samples)
+                .filter(|_| {
+                    material
+                        .scatter(&mut rng, &incoming, &normal, front_face, None)
+                        .is_some()
+                })
+                .count();
+            let expected = scattered as f64 / samples as f64;
+            assert!((total - expected).abs() < 0.01);
+        }
+    }
+}
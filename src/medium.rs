@@ -0,0 +1,90 @@
+use crate::{
+    bounding_box::{Aabb, BoundingBox},
+    hittable::{Hit, Hittable},
+    material::Isotropic,
+    ray::Ray,
+    shape::Puncture,
+    texture::Solid,
+    vec3::Vec3,
+};
+use rand::{Rng, RngCore};
+
+/// Volume of constant density, like fog or smoke, filling the inside of the boundary. The boundary
+/// must be convex, since only the first stretch of the ray inside of it is considered.
+pub struct ConstantMedium<H> {
+    boundary: H,
+    /// Probability per unit of length for a ray to scatter within the medium.
+    density: f64,
+    phase_function: Solid<Isotropic>,
+}
+
+impl<H> ConstantMedium<H> {
+    /// `density` must be positive.
+    pub fn new(boundary: H, density: f64, phase_function: Isotropic) -> Self {
+        debug_assert!(density > 0.);
+        Self {
+            boundary,
+            density,
+            phase_function: Solid(phase_function),
+        }
+    }
+}
+
+impl<H> Hittable for ConstantMedium<H>
+where
+    H: Hittable,
+{
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        // Find where the ray enters and leaves the boundary. The entry point may well be behind
+        // the ray origin, if the ray starts within the medium.
+        let (enter, _) = self
+            .boundary
+            .hit(ray, f64::NEG_INFINITY, f64::INFINITY, time, rng)?;
+        let (leave, _) = self
+            .boundary
+            .hit(ray, enter + 0.0001, f64::INFINITY, time, rng)?;
+
+        let enter = enter.max(t_min).max(0.);
+        let leave = leave.min(t_max);
+        if enter >= leave {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (leave - enter) * ray_length;
+        // Free flight distance is exponentially distributed. `1 - u` avoids taking the logarithm
+        // of zero.
+        let u: f64 = rng.gen_range(0., 1.);
+        let hit_distance = -(1. - u).ln() / self.density;
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = enter + hit_distance / ray_length;
+        // Normal and face are meaningless within a volume. The isotropic phase function ignores
+        // them anyway.
+        let intersection = Puncture {
+            point: ray.at(t),
+            front_face: true,
+            normal: Vec3::new(1., 0., 0.),
+            texture_coordiantes: (0., 0.),
+        };
+        Some((t, Hit::new(intersection, &self.phase_function)))
+    }
+}
+
+impl<B> BoundingBox for ConstantMedium<B>
+where
+    B: BoundingBox,
+{
+    fn bounding_box(&self, exposure_time: f64) -> Aabb {
+        self.boundary.bounding_box(exposure_time)
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    shape::Triangle,
+    vec3::{Point, Vec3},
+};
+use std::{
+    fs::read_to_string,
+    io::{self, ErrorKind},
+    path::Path,
+    str::SplitWhitespace,
+};
+
+/// Loads all faces of a Wavefront OBJ file as triangles. Faces with more than three vertices are
+/// split into a triangle fan. Positions, normals and texture coordinates are honored, everything
+/// else (groups, materials, ...) is ignored. Fails for files without any faces.
+pub fn load_obj(path: impl AsRef<Path>) -> io::Result<Vec<Triangle>> {
+    let text = read_to_string(path)?;
+    parse_obj(&text)
+}
+
+fn parse_obj(text: &str) -> io::Result<Vec<Triangle>> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texture_coordinates = Vec::new();
+    let mut triangles = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let invalid = |message: &str| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid OBJ file. Line {}: {}", line_number, message),
+            )
+        };
+        // Strip comments
+        let line = line.split('#').next().unwrap();
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let [x, y, z] = parse_floats(&mut tokens).ok_or_else(|| invalid("Bad vertex."))?;
+                positions.push(Point::new(x, y, z));
+            }
+            Some("vn") => {
+                let [x, y, z] = parse_floats(&mut tokens).ok_or_else(|| invalid("Bad normal."))?;
+                normals.push(Vec3::new(x, y, z).unit());
+            }
+            Some("vt") => {
+                let [u, v] =
+                    parse_floats(&mut tokens).ok_or_else(|| invalid("Bad texture coordinate."))?;
+                texture_coordinates.push((u, v));
+            }
+            Some("f") => {
+                let face = tokens
+                    .map(|token| {
+                        FaceVertex::parse(
+                            token,
+                            positions.len(),
+                            texture_coordinates.len(),
+                            normals.len(),
+                        )
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid("Bad face."))?;
+                if face.len() < 3 {
+                    return Err(invalid("Face with less than three vertices."));
+                }
+                for i in 1..face.len() - 1 {
+                    let corners = [face[0], face[i], face[i + 1]];
+                    let vertices = [
+                        positions[corners[0].position],
+                        positions[corners[1].position],
+                        positions[corners[2].position],
+                    ];
+                    let normals = match (corners[0].normal, corners[1].normal, corners[2].normal) {
+                        (Some(n0), Some(n1), Some(n2)) => {
+                            Some([normals[n0], normals[n1], normals[n2]])
+                        }
+                        _ => None,
+                    };
+                    let texture_coordinates = match (
+                        corners[0].texture_coordinates,
+                        corners[1].texture_coordinates,
+                        corners[2].texture_coordinates,
+                    ) {
+                        (Some(t0), Some(t1), Some(t2)) => Some([
+                            texture_coordinates[t0],
+                            texture_coordinates[t1],
+                            texture_coordinates[t2],
+                        ]),
+                        _ => None,
+                    };
+                    triangles.push(Triangle::new(vertices, normals, texture_coordinates));
+                }
+            }
+            // Empty line, or a statement we do not care about.
+            _ => (),
+        }
+    }
+
+    if triangles.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Invalid OBJ file. It does not contain any faces.",
+        ));
+    }
+    Ok(triangles)
+}
+
+/// Parses the next `N` tokens as floats. Additional tokens are ignored (e.g. the optional `w`
+/// component of a vertex).
+fn parse_floats<const N: usize>(tokens: &mut SplitWhitespace) -> Option<[f64; N]> {
+    let mut values = [0.; N];
+    for value in &mut values {
+        *value = tokens.next()?.parse().ok()?;
+    }
+    Some(values)
+}
+
+/// Zero based indices of the attributes of one corner of a face.
+#[derive(Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    texture_coordinates: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl FaceVertex {
+    /// Parses tokens like `1`, `1/2`, `1//3` or `1/2/3`. The lengths of the attribute lists are
+    /// required to resolve negative (relative) indices and to check bounds.
+    fn parse(
+        token: &str,
+        num_positions: usize,
+        num_texture_coordinates: usize,
+        num_normals: usize,
+    ) -> Option<Self> {
+        let mut parts = token.split('/');
+        let position = resolve_index(parts.next()?, num_positions)?;
+        let texture_coordinates = match parts.next() {
+            None | Some("") => None,
+            Some(index) => Some(resolve_index(index, num_texture_coordinates)?),
+        };
+        let normal = match parts.next() {
+            None | Some("") => None,
+            Some(index) => Some(resolve_index(index, num_normals)?),
+        };
+        Some(Self {
+            position,
+            texture_coordinates,
+            normal,
+        })
+    }
+}
+
+/// OBJ indices start at one. Negative indices count backwards from the last element defined so far.
+fn resolve_index(token: &str, len: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let index = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if 0 <= index && (index as usize) < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray::Ray, shape::Shape};
+
+    /// Shoots a ray straight down onto the `z = 0` plane at `(x, y)`.
+    fn hit_from_above(triangle: &Triangle, x: f64, y: f64) -> Option<(f64, f64)> {
+        let ray = Ray::new(Point::new(x, y, 1.), Vec3::new(0., 0., -1.));
+        triangle
+            .intersect(&ray, 0., f64::INFINITY)
+            .map(|(_, puncture)| puncture.texture_coordiantes)
+    }
+
+    #[test]
+    fn splits_polygons_into_triangle_fans() {
+        let obj = "\
+            # Unit square\n\
+            v 0 0 0\n\
+            v 1 0 0\n\
+            v 1 1 0\n\
+            v 0 1 0\n\
+            f 1 2 3 4\n";
+        let triangles = parse_obj(obj).unwrap();
+        assert_eq!(triangles.len(), 2);
+        // Together the two triangles cover the square.
+        for &(x, y) in &[(0.9, 0.1), (0.1, 0.9)] {
+            assert!(triangles
+                .iter()
+                .any(|triangle| hit_from_above(triangle, x, y).is_some()));
+        }
+    }
+
+    #[test]
+    fn resolves_relative_indices_and_texture_coordinates() {
+        let obj = "\
+            v 0 0 0\n\
+            v 1 0 0\n\
+            v 0 1 0\n\
+            vt 0.5 0.5\n\
+            vt 1 0.5\n\
+            vt 0.5 1\n\
+            vn 0 0 1\n\
+            f -3/-3/1 -2/-2/1 -1/-1/1\n";
+        let triangles = parse_obj(obj).unwrap();
+        let (u, v) = hit_from_above(&triangles[0], 0.5, 0.).unwrap();
+        assert!((u - 0.75).abs() < 1e-9);
+        assert!((v - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reports_the_line_of_bad_faces() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n";
+        let error = parse_obj(obj).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("Line 4"));
+    }
+
+    #[test]
+    fn rejects_files_without_faces() {
+        let error = parse_obj("v 0 0 0\n").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
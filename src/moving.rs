@@ -4,6 +4,7 @@ use crate::{
     ray::Ray,
     vec3::Vec3,
 };
+use rand::RngCore;
 
 pub struct Moving<H> {
     velocity: Vec3,
@@ -20,11 +21,18 @@ impl<H> Hittable for Moving<H>
 where
     H: Hittable,
 {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, time: f64) -> Option<(f64, Hit)> {
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
         let mut ray_in_object_coordinates = *ray;
         ray_in_object_coordinates.origin -= self.velocity * time;
         self.inner
-            .hit(&ray_in_object_coordinates, t_min, t_max, time)
+            .hit(&ray_in_object_coordinates, t_min, t_max, time, rng)
             .map(|(distance, mut hit_record)| {
                 hit_record.intersection.point += self.velocity * time;
                 (distance, hit_record)
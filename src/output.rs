@@ -1,13 +1,29 @@
-use crate::Color;
+use crate::{
+    image_file::{is_float_format, save_linear},
+    tone_mapping::{linear_to_srgb, Display},
+    Color,
+};
 use image::ImageBuffer;
 use std::{io, path::Path};
 
-pub fn save_image(color_buf: &[Color], image_width: u32, output: &Path) -> io::Result<()> {
+/// Saves the rendered image. `.exr` and `.pfm` files receive the linear radiance as floating point
+/// values, unaffected by `display`. Any other format is tone mapped according to `display`, sRGB
+/// encoded and quantized to 8 bit.
+pub fn save_image(
+    color_buf: &[Color],
+    image_width: u32,
+    output: &Path,
+    display: &Display,
+) -> io::Result<()> {
+    if is_float_format(output) {
+        return save_linear(output, image_width as usize, color_buf);
+    }
+
     let mut image_buffer =
         ImageBuffer::new(image_width, (color_buf.len() / image_width as usize) as u32);
 
     for (x, y, pixel) in image_buffer.enumerate_pixels_mut() {
-        let rgb = gamma_corrected_rgb(&color_buf[(y * image_width + x) as usize]);
+        let rgb = srgb_bytes(&display.map(&color_buf[(y * image_width + x) as usize]));
         *pixel = image::Rgb(rgb);
     }
 
@@ -20,7 +36,7 @@ pub fn save_image(color_buf: &[Color], image_width: u32, output: &Path) -> io::R
                 eprintln!("I'll try to save into an `.png` file instead.");
                 let mut new_path = output.to_path_buf();
                 new_path.set_extension("png");
-                save_image(color_buf, image_width, &new_path)?;
+                save_image(color_buf, image_width, &new_path, display)?;
             } else {
                 panic!("Unexpected error saving to image file: {}", e)
             }
@@ -31,19 +47,8 @@ pub fn save_image(color_buf: &[Color], image_width: u32, output: &Path) -> io::R
     Ok(())
 }
 
-fn gamma_corrected_rgb(color: &Color) -> [u8; 3] {
-    let red = (256. * clamp(color[0].sqrt(), 0., 0.999)) as u8;
-    let green = (256. * clamp(color[1].sqrt(), 0., 0.999)) as u8;
-    let blue = (256. * clamp(color[2].sqrt(), 0., 0.999)) as u8;
-    [red, green, blue]
-}
-
-fn clamp(f: f64, low: f64, high: f64) -> f64 {
-    if f < low {
-        low
-    } else if f > high {
-        high
-    } else {
-        f
-    }
+/// Encodes a displayable linear color with channels in [0, 1].
+fn srgb_bytes(color: &Color) -> [u8; 3] {
+    let encode = |value: f64| (255. * linear_to_srgb(value.clamp(0., 1.))).round() as u8;
+    [encode(color[0]), encode(color[1]), encode(color[2])]
 }
@@ -4,11 +4,7 @@ use crate::{
     texture::Texture,
     vec3::{dot, Color, Point, Vec3},
 };
-use rand::{
-    rngs::{StdRng, ThreadRng},
-    seq::SliceRandom,
-    SeedableRng,
-};
+use rand::{rngs::StdRng, seq::SliceRandom, RngCore, SeedableRng};
 
 pub struct Perlin {
     randoms: Vec<Vec3>,
@@ -41,6 +37,7 @@ impl Perlin {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn noise(&self, p: &Point) -> f64 {
         let u = p.x() - p.x().floor();
         let v = p.y() - p.y().floor();
@@ -65,6 +62,7 @@ impl Perlin {
         Self::perlin_interpolation(&c, u, v, w)
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn perlin_interpolation(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
         // hermetian smoothing
         let uu = u * u * (3. - 2. * u);
@@ -86,6 +84,14 @@ impl Perlin {
         accum
     }
 
+    fn material(&self, punctured: &Puncture) -> Lambertian {
+        let albedo =
+            // Color::new(1., 1., 1.) * 0.5 * (1.0 + self.noise(&(punctured.point * self.scale)));
+            // Color::new(1., 1., 1.) * self.turbulence(&(punctured.point * self.scale), 7);
+            Color::new(1.,1.,1.) * 0.5 * (1. + (self.scale * punctured.point.z() + 10. *self.turbulence(&punctured.point, 7)).sin());
+        Lambertian::new(albedo)
+    }
+
     fn turbulence(&self, p: &Point, depth: u32) -> f64 {
         let mut accum = 0.0;
         let mut temp_p = *p;
@@ -104,14 +110,33 @@ impl Perlin {
 impl Texture for Perlin {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         punctured: &Puncture,
         incoming: &Vec3,
+        wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
-        let albedo =
-            // Color::new(1., 1., 1.) * 0.5 * (1.0 + self.noise(&(punctured.point * self.scale)));
-            // Color::new(1., 1., 1.) * self.turbulence(&(punctured.point * self.scale), 7);
-            Color::new(1.,1.,1.) * 0.5 * (1. + (self.scale * punctured.point.z() + 10. *self.turbulence(&punctured.point, 7)).sin());
-        Lambertian::new(albedo).scatter(rng, incoming, &punctured.normal, punctured.front_face)
+        self.material(punctured).scatter(
+            rng,
+            incoming,
+            &punctured.normal,
+            punctured.front_face,
+            wavelength,
+        )
+    }
+
+    fn evaluate(
+        &self,
+        punctured: &Puncture,
+        incoming: &Vec3,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        self.material(punctured).evaluate(
+            incoming,
+            &punctured.normal,
+            punctured.front_face,
+            direction,
+            wavelength,
+        )
     }
 }
@@ -1,22 +1,52 @@
 use crate::{
-    bvh::{into_bounding_volume_hierarchy, BoundedHittable},
+    background::Background,
+    bvh::{into_bounded_hierarchy, into_bounding_volume_hierarchy, BoundedHittable, BvhStrategy},
     camera::Camera,
-    material::{Dielectric, Lambertian, Metal},
+    environment::EnvironmentMap,
+    hittable::Textured,
+    image_texture::ImageTexture,
+    lights::{Light, Lights},
+    material::{
+        Conductor, Dielectric, DiffuseLight, Isotropic, Lambertian, Metal, Principled,
+        PrincipledParameters, RefractiveIndex, RoughDielectric,
+    },
+    medium::ConstantMedium,
+    mesh::load_obj,
     moving::Moving,
     perlin::Perlin,
     scene::Scene,
-    shape::Sphere,
+    shape::{AxisBox, AxisRect, BoundedShape, Plane, Sphere, Triangle},
     texture::{Checkerd, Solid, Texture},
+    tone_mapping::Display,
+    transform::{Matrix4, Transform},
     vec3::{Color, Point, Vec3},
 };
 use serde::{Deserialize, Serialize};
-use std::{fs::read_to_string, io, path::Path};
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 /// Serializable representation of a Scene. Used to persist scenes to '.toml' files.
 #[derive(Serialize, Deserialize)]
 pub struct SceneBuilder {
     pub camera: CameraBuilder,
     pub world: Vec<HittableBuilder>,
+    /// Light coming from rays not hitting any object. Defaults to a blue sky gradient.
+    #[serde(default)]
+    pub background: BackgroundBuilder,
+    /// Participating media like fog or smoke.
+    #[serde(default)]
+    pub volumes: Vec<VolumeBuilder>,
+    /// Tone mapping and exposure of 8 bit output images.
+    #[serde(default)]
+    pub display: Display,
+    /// How the bounding volume hierarchy is built. Defaults to the surface area heuristic.
+    #[serde(default)]
+    pub bvh: BvhStrategy,
 }
 
 impl SceneBuilder {
@@ -31,14 +61,56 @@ impl SceneBuilder {
         std::fs::write(&path, text)
     }
 
-    pub fn build(&self) -> Scene {
-        let hittables = self.world.iter().map(|model| model.build()).collect();
-        let world = into_bounding_volume_hierarchy(hittables, self.camera.exposure_time);
+    /// Identifies the content of the scene, ignoring settings which only affect the output (e.g.
+    /// tone mapping). External resources like meshes are identified by their path only.
+    pub fn content_hash(&self) -> u64 {
+        let mut description = serde_json::to_value(self).unwrap();
+        let description_object = description.as_object_mut().unwrap();
+        description_object.remove("display");
+        description_object.remove("bvh");
+        // FNV-1a. Unlike the hasher of the standard library it is guaranteed to stay the same
+        // across versions, so checkpoints remain valid.
+        description
+            .to_string()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    /// Fails if external resources referenced by the scene (e.g. meshes) can not be loaded, or if
+    /// the scene can not be rendered (e.g. because an object is scaled by zero). Also checks the
+    /// display settings, which are only needed when saving the image.
+    pub fn build(&self) -> io::Result<Scene> {
+        if !self.display.is_valid() {
+            return Err(invalid_input(
+                "The white point of the extended Reinhard tone mapper must be positive and the \
+                exposure finite.",
+            ));
+        }
+        let mut hittables = Vec::new();
+        let mut lights = Vec::new();
+        // Meshes placed multiple times within the scene are only loaded once.
+        let mut meshes = HashMap::new();
+        for model in &self.world {
+            let (hittable, model_lights) =
+                model.build(&mut meshes, self.camera.exposure_time, self.bvh)?;
+            hittables.push(hittable);
+            lights.extend(model_lights);
+        }
+        for volume in &self.volumes {
+            hittables.push(volume.build(&mut meshes, self.camera.exposure_time, self.bvh)?);
+        }
+        let world = into_bounding_volume_hierarchy(hittables, self.camera.exposure_time, self.bvh);
         // let hittables: Vec<_> = self.world.iter().map(|model| model.build()).collect();
         // let world = Box::new(hittables);
         let camera = self.camera.build();
 
-        Scene::new(world, camera)
+        let background = self.background.build()?;
+
+        let lights = Lights::new(lights);
+
+        Ok(Scene::new(world, camera, background, lights))
     }
 }
 
@@ -74,40 +146,404 @@ impl CameraBuilder {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub enum BackgroundBuilder {
+    /// Pitch black. For scenes which are lit only by glowing objects.
+    None,
+    Solid {
+        color: Color,
+    },
+    /// Vertical gradient from `bottom` (looking straight down) to `top` (looking straight up).
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// Equirectangular image surrounding the scene, ideally a Radiance `.hdr` or `.pfm` file.
+    /// `rotation` turns the map around the vertical axis and is given in degrees. `intensity`
+    /// scales the brightness of every pixel.
+    Environment {
+        path: PathBuf,
+        rotation: f64,
+        intensity: f64,
+    },
+}
+
+impl Default for BackgroundBuilder {
+    fn default() -> Self {
+        BackgroundBuilder::Gradient {
+            bottom: Color::new(1., 1., 1.),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl BackgroundBuilder {
+    fn build(&self) -> io::Result<Background> {
+        let background = match self {
+            BackgroundBuilder::None => Background::Black,
+            BackgroundBuilder::Solid { color } => Background::Solid(*color),
+            BackgroundBuilder::Gradient { bottom, top } => Background::Gradient {
+                bottom: *bottom,
+                top: *top,
+            },
+            BackgroundBuilder::Environment {
+                path,
+                rotation,
+                intensity,
+            } => Background::Environment(EnvironmentMap::from_path(path, *rotation, *intensity)?),
+        };
+        Ok(background)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub enum SurfaceBuilder {
-    Diffuse { albedo: Color },
-    Metal { albedo: Color, fuzziness: f64 },
-    Dielectric { refractive_index: f64 },
+    Diffuse {
+        albedo: Color,
+    },
+    Metal {
+        albedo: Color,
+        fuzziness: f64,
+    },
+    /// Metal with a physically based microfacet model. `roughness` ranges from 0 (mirror) to 1.
+    Conductor {
+        ior: ConductorIor,
+        roughness: f64,
+    },
+    /// Transparent material like glass or water. `refractive_index` is either a number, or a
+    /// dispersion formula like `{"Cauchy": {"a": 1.5046, "b": 0.0042}}`.
+    Dielectric {
+        refractive_index: RefractiveIndex,
+        /// Tints light passing through the material. Defaults to clear.
+        #[serde(default)]
+        absorption: Absorption,
+    },
+    /// Transparent material with a rough surface, like frosted glass. `roughness` ranges from 0
+    /// (smooth) to 1.
+    RoughDielectric {
+        refractive_index: RefractiveIndex,
+        roughness: f64,
+        #[serde(default)]
+        absorption: Absorption,
+    },
+    /// Artist friendly material, which covers diffuse, metallic, glossy and transparent surfaces.
+    /// E.g. `{"Principled": {"base_color": [0.8, 0.1, 0.1], "clearcoat": 1}}` for red car paint.
+    Principled(PrincipledParameters),
     Checkered(Box<SurfaceBuilder>, Box<SurfaceBuilder>),
-    Perlin { seed: u64, scale: f64 },
+    Perlin {
+        seed: u64,
+        scale: f64,
+    },
+    /// Glowing surface. Use components larger than one for bright lights.
+    DiffuseLight {
+        emit: Color,
+    },
+    /// Diffuse surface with its color looked up from an image file (e.g. PNG or JPEG) using the
+    /// texture coordinates of the shape.
+    Image {
+        path: PathBuf,
+    },
 }
 
 impl SurfaceBuilder {
-    fn build(&self) -> Box<dyn Texture + Send + Sync> {
-        match self {
-            SurfaceBuilder::Diffuse { albedo } => Box::new(Solid(Lambertian::new(*albedo))),
+    fn build(&self) -> io::Result<Arc<dyn Texture + Send + Sync>> {
+        let texture: Arc<dyn Texture + Send + Sync> = match self {
+            SurfaceBuilder::Diffuse { albedo } => Arc::new(Solid(Lambertian::new(*albedo))),
             SurfaceBuilder::Metal { albedo, fuzziness } => {
-                Box::new(Solid(Metal::new(*albedo, *fuzziness)))
+                Arc::new(Solid(Metal::new(*albedo, *fuzziness)))
+            }
+            SurfaceBuilder::Conductor { ior, roughness } => {
+                let (eta, k) = ior.eta_k();
+                Arc::new(Solid(Conductor::new(eta, k, *roughness)))
             }
-            SurfaceBuilder::Dielectric { refractive_index } => {
-                Box::new(Solid(Dielectric::new(*refractive_index)))
+            SurfaceBuilder::Dielectric {
+                refractive_index,
+                absorption,
+            } => Arc::new(Solid(Dielectric::new(
+                *refractive_index,
+                absorption.coefficient()?,
+            ))),
+            SurfaceBuilder::RoughDielectric {
+                refractive_index,
+                roughness,
+                absorption,
+            } => Arc::new(Solid(RoughDielectric::new(
+                *refractive_index,
+                absorption.coefficient()?,
+                *roughness,
+            ))),
+            SurfaceBuilder::Principled(parameters) => {
+                if !parameters.is_valid() {
+                    return Err(invalid_input(
+                        "Parameters of principled materials must lie within [0, 1].",
+                    ));
+                }
+                Arc::new(Solid(Principled::new(*parameters)))
             }
-            SurfaceBuilder::Checkered(t0, t1) => Box::new(Checkerd::new(t0.build(), t1.build())),
-            SurfaceBuilder::Perlin { seed, scale } => Box::new(Perlin::new(*seed, *scale)),
+            SurfaceBuilder::Checkered(t0, t1) => Arc::new(Checkerd::new(t0.build()?, t1.build()?)),
+            SurfaceBuilder::Perlin { seed, scale } => Arc::new(Perlin::new(*seed, *scale)),
+            SurfaceBuilder::DiffuseLight { emit } => Arc::new(Solid(DiffuseLight::new(*emit))),
+            SurfaceBuilder::Image { path } => Arc::new(ImageTexture::from_path(path)?),
+        };
+        Ok(texture)
+    }
+}
+
+/// Light absorbed while travelling through the inside of a transparent material (Beer–Lambert
+/// law). The longer the way through the material, the stronger the tint.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub enum Absorption {
+    /// Clear material.
+    #[default]
+    None,
+    /// Absorption coefficient per unit length, for each color channel.
+    Coefficient(Color),
+    /// Color of white light after travelling `distance` through the material. More intuitive to
+    /// specify than the coefficient. Components must be larger than zero and at most one.
+    Transmission { color: Color, distance: f64 },
+}
+
+impl Absorption {
+    /// Fails for coefficients which would amplify light or are not finite.
+    fn coefficient(&self) -> io::Result<Color> {
+        let coefficient = match self {
+            Absorption::None => Color::ZERO,
+            Absorption::Coefficient(coefficient) => *coefficient,
+            Absorption::Transmission { color, distance } => {
+                let valid = *distance > 0. && color.iter().all(|&c| c > 0. && c <= 1.);
+                if !valid {
+                    return Err(invalid_input(
+                        "Transmission color components must lie in (0, 1] and distance must be \
+                        positive.",
+                    ));
+                }
+                Color::new(
+                    -color[0].ln() / distance,
+                    -color[1].ln() / distance,
+                    -color[2].ln() / distance,
+                )
+            }
+        };
+        if !coefficient.iter().all(|&c| c >= 0. && c.is_finite()) {
+            return Err(invalid_input(
+                "Absorption coefficients must be finite and not negative.",
+            ));
         }
+        Ok(coefficient)
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+/// Complex index of refraction of a metal, either one of the presets or custom values.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum ConductorIor {
+    Gold,
+    Copper,
+    Silver,
+    Aluminium,
+    /// Real part `eta` and extinction coefficient `k` for the red, green and blue channel.
+    Custom {
+        eta: Color,
+        k: Color,
+    },
+}
+
+impl ConductorIor {
+    /// Presets are measured values, sampled at wavelengths of roughly 650, 550 and 450 nm.
+    fn eta_k(&self) -> (Color, Color) {
+        match self {
+            ConductorIor::Gold => (
+                Color::new(0.143, 0.374, 1.442),
+                Color::new(3.983, 2.385, 1.603),
+            ),
+            ConductorIor::Copper => (
+                Color::new(0.200, 0.924, 1.102),
+                Color::new(3.912, 2.452, 2.142),
+            ),
+            ConductorIor::Silver => (
+                Color::new(0.155, 0.117, 0.138),
+                Color::new(4.828, 3.122, 2.147),
+            ),
+            ConductorIor::Aluminium => (
+                Color::new(1.657, 0.880, 0.521),
+                Color::new(9.224, 6.270, 4.837),
+            ),
+            ConductorIor::Custom { eta, k } => (*eta, *k),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub enum ShapeBuilder {
-    Sphere { center: Point, radius: f64 },
+    Sphere {
+        center: Point,
+        radius: f64,
+    },
+    /// Vertices are expected in counter clockwise order, seen from the outside.
+    Triangle {
+        vertices: [Point; 3],
+    },
+    /// Triangle mesh loaded from a Wavefront OBJ file. Relative paths are interpreted relative to
+    /// the working directory.
+    Mesh {
+        path: PathBuf,
+    },
+    /// Rectangle at `z = k`, facing towards `+z`.
+    XyRect {
+        x0: f64,
+        x1: f64,
+        y0: f64,
+        y1: f64,
+        k: f64,
+    },
+    /// Rectangle at `y = k`, facing towards `+y`.
+    XzRect {
+        x0: f64,
+        x1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+    },
+    /// Rectangle at `x = k`, facing towards `+x`.
+    YzRect {
+        y0: f64,
+        y1: f64,
+        z0: f64,
+        z1: f64,
+        k: f64,
+    },
+    /// Axis aligned box spanned by two opposing corners. `min` must be smaller than `max` along
+    /// every axis.
+    AxisBox {
+        min: Point,
+        max: Point,
+    },
+    /// Plane through `point`, facing towards `normal`. It ends at distance `extent` from `point`,
+    /// so it can be part of the bounding volume hierarchy. Defaults to 10000 units.
+    Plane {
+        point: Point,
+        normal: Vec3,
+        extent: Option<f64>,
+    },
 }
 
 impl ShapeBuilder {
-    fn build(self) -> Sphere {
+    fn build(
+        &self,
+        meshes: &mut HashMap<PathBuf, Arc<Mesh>>,
+        exposure_time: f64,
+        bvh: BvhStrategy,
+    ) -> io::Result<Geometry> {
+        let shape: Arc<dyn BoundedShape> = match self {
+            ShapeBuilder::Sphere { center, radius } => Arc::new(Sphere::new(*center, *radius)),
+            ShapeBuilder::Triangle { vertices } => Arc::new(Triangle::new(*vertices, None, None)),
+            ShapeBuilder::XyRect { x0, x1, y0, y1, k } => {
+                Arc::new(AxisRect::xy(span(*x0, *x1)?, span(*y0, *y1)?, *k))
+            }
+            ShapeBuilder::XzRect { x0, x1, z0, z1, k } => {
+                Arc::new(AxisRect::xz(span(*x0, *x1)?, span(*z0, *z1)?, *k))
+            }
+            ShapeBuilder::YzRect { y0, y1, z0, z1, k } => {
+                Arc::new(AxisRect::yz(span(*y0, *y1)?, span(*z0, *z1)?, *k))
+            }
+            ShapeBuilder::AxisBox { min, max } => {
+                let valid = (0..3).all(|axis| {
+                    min[axis] < max[axis] && min[axis].is_finite() && max[axis].is_finite()
+                });
+                if !valid {
+                    return Err(invalid_input(
+                        "The minimum corner of a box must be smaller than its maximum corner \
+                        along every axis.",
+                    ));
+                }
+                Arc::new(AxisBox::new(*min, *max))
+            }
+            ShapeBuilder::Plane {
+                point,
+                normal,
+                extent,
+            } => {
+                let length = normal.length();
+                if length == 0. || !length.is_finite() {
+                    return Err(invalid_input("The normal of a plane must not be zero."));
+                }
+                let extent = extent.unwrap_or(Plane::DEFAULT_EXTENT);
+                if !(extent > 0. && extent.is_finite()) {
+                    return Err(invalid_input("The extent of a plane must be positive."));
+                }
+                Arc::new(Plane::new(*point, *normal, extent))
+            }
+            ShapeBuilder::Mesh { path } => {
+                if !meshes.contains_key(path) {
+                    let mesh = Mesh::new(load_obj(path)?, exposure_time, bvh);
+                    meshes.insert(path.clone(), Arc::new(mesh));
+                }
+                return Ok(Geometry::Mesh(meshes[path].clone()));
+            }
+        };
+        Ok(Geometry::Primitive(shape))
+    }
+}
+
+/// Side of a rectangle. Fails if it has no length, since the rectangle would have no area.
+fn span(start: f64, end: f64) -> io::Result<(f64, f64)> {
+    if start == end || !(start - end).is_finite() {
+        return Err(invalid_input(
+            "Rectangles must have a non zero, finite extent along both axes.",
+        ));
+    }
+    Ok((start, end))
+}
+
+/// Result of building a [`ShapeBuilder`]. Cheap to clone.
+enum Geometry {
+    Primitive(Arc<dyn BoundedShape>),
+    Mesh(Arc<Mesh>),
+}
+
+impl Geometry {
+    /// The individual surfaces, e.g. each triangle of a mesh.
+    fn shapes(&self) -> Vec<Arc<dyn BoundedShape>> {
+        match self {
+            Geometry::Primitive(shape) => vec![shape.clone()],
+            Geometry::Mesh(mesh) => mesh.triangles.clone(),
+        }
+    }
+
+    fn with_texture(&self, texture: Arc<dyn Texture + Send + Sync>) -> Box<dyn BoundedHittable> {
         match self {
-            ShapeBuilder::Sphere { center, radius } => Sphere::new(center, radius),
+            Geometry::Primitive(shape) => Box::new((shape.clone(), texture)),
+            Geometry::Mesh(mesh) => Box::new(Textured::new(mesh.hierarchy.clone(), texture)),
+        }
+    }
+}
+
+/// Triangles of a mesh loaded from a file, together with a bounding volume hierarchy over them.
+/// Every placement of the mesh shares the same hierarchy, instead of building its own.
+struct Mesh {
+    triangles: Vec<Arc<dyn BoundedShape>>,
+    hierarchy: Arc<dyn BoundedHittable>,
+}
+
+impl Mesh {
+    fn new(triangles: Vec<Triangle>, exposure_time: f64, bvh: BvhStrategy) -> Self {
+        let triangles: Vec<Arc<dyn BoundedShape>> = triangles
+            .into_iter()
+            .map(|triangle| Arc::new(triangle) as Arc<dyn BoundedShape>)
+            .collect();
+        // Each placement shows the mesh with its own material, so the texture the hierarchy is
+        // built with is never seen.
+        let placeholder: Arc<dyn Texture + Send + Sync> =
+            Arc::new(Solid(Lambertian::new(Color::ZERO)));
+        let hittables = triangles
+            .iter()
+            .map(|triangle| {
+                Box::new((triangle.clone(), placeholder.clone())) as Box<dyn BoundedHittable>
+            })
+            .collect();
+        let hierarchy = into_bounded_hierarchy(hittables, exposure_time, bvh);
+        Self {
+            triangles,
+            hierarchy: Arc::from(hierarchy),
         }
     }
 }
@@ -116,18 +552,302 @@ impl ShapeBuilder {
 pub struct HittableBuilder {
     pub shape: ShapeBuilder,
     pub material: SurfaceBuilder,
-    pub velocity: Option<Vec3>,
+    #[serde(flatten)]
+    pub placement: Placement,
 }
 
 impl HittableBuilder {
-    fn build(&self) -> Box<dyn BoundedHittable> {
+    /// The object together with the lights it consists of, if it glows. Lights share their shapes
+    /// with the object. Glowing objects which are scaled non uniformly can not be sampled as
+    /// lights. They still illuminate the scene, but only rays scattered towards them by chance
+    /// find them.
+    fn build(
+        &self,
+        meshes: &mut HashMap<PathBuf, Arc<Mesh>>,
+        exposure_time: f64,
+        bvh: BvhStrategy,
+    ) -> io::Result<(Box<dyn BoundedHittable>, Vec<Light>)> {
+        let texture = self.material.build()?;
+        let geometry = self.shape.build(meshes, exposure_time, bvh)?;
+        let hittable = self.placement.place(geometry.with_texture(texture))?;
+        let lights = match self.material {
+            SurfaceBuilder::DiffuseLight { emit } => geometry
+                .shapes()
+                .into_iter()
+                .filter_map(|shape| {
+                    Light::new(shape, emit)
+                        .placed(self.placement.transformation(), self.placement.velocity)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok((hittable, lights))
+    }
+}
+
+/// Fog or smoke filling the inside of a shape.
+#[derive(Serialize, Deserialize)]
+pub struct VolumeBuilder {
+    /// Must be convex.
+    pub boundary: ShapeBuilder,
+    /// Probability per unit of length for a ray to scatter within the volume. Larger values make
+    /// the volume more opaque. Must be positive.
+    pub density: f64,
+    /// Color of the scattered light.
+    pub albedo: Color,
+    #[serde(flatten)]
+    pub placement: Placement,
+}
+
+impl VolumeBuilder {
+    fn build(
+        &self,
+        meshes: &mut HashMap<PathBuf, Arc<Mesh>>,
+        exposure_time: f64,
+        bvh: BvhStrategy,
+    ) -> io::Result<Box<dyn BoundedHittable>> {
+        if !(self.density > 0. && self.density.is_finite()) {
+            return Err(invalid_input(
+                "Density of volumes must be positive and finite.",
+            ));
+        }
+        // The surface of the boundary is never rendered, so any texture will do.
+        let texture = Arc::new(Solid(Isotropic::new(self.albedo)));
+        let geometry = self.boundary.build(meshes, exposure_time, bvh)?;
+        let boundary = self.placement.place(geometry.with_texture(texture))?;
+        Ok(Box::new(ConstantMedium::new(
+            boundary,
+            self.density,
+            Isotropic::new(self.albedo),
+        )))
+    }
+}
+
+/// Position, orientation, size and movement of an object within the scene.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Placement {
+    pub velocity: Option<Vec3>,
+    /// Scaling factors along the x, y and z axis. Applied first.
+    pub scale: Option<Vec3>,
+    /// Rotation in degrees around the x, y and z axis (in this order). Applied after scaling.
+    pub rotate: Option<Vec3>,
+    /// Offset applied after scaling and rotating.
+    pub translate: Option<Vec3>,
+}
+
+impl Placement {
+    fn place(
+        &self,
+        mut hittable: Box<dyn BoundedHittable>,
+    ) -> io::Result<Box<dyn BoundedHittable>> {
+        if let Some(matrix) = self.transformation() {
+            hittable = Box::new(Transform::new(matrix, hittable).ok_or_else(|| {
+                invalid_input("Transformation must be invertible. Check for a scale of zero.")
+            })?);
+        }
         if let Some(velocity) = self.velocity {
-            Box::new(Moving::new(
-                velocity,
-                (self.shape.build(), self.material.build()),
-            ))
-        } else {
-            Box::new((self.shape.build(), self.material.build()))
+            hittable = Box::new(Moving::new(velocity, hittable));
+        }
+        Ok(hittable)
+    }
+
+    /// `None` if neither `scale`, `rotate` nor `translate` are specified.
+    fn transformation(&self) -> Option<Matrix4> {
+        if self.scale.is_none() && self.rotate.is_none() && self.translate.is_none() {
+            return None;
+        }
+        let mut matrix = Matrix4::IDENTITY;
+        if let Some(factors) = &self.scale {
+            matrix = Matrix4::scaling(factors);
+        }
+        if let Some(degrees) = &self.rotate {
+            for axis in 0..3 {
+                matrix = Matrix4::rotation(axis, degrees[axis]) * matrix;
+            }
+        }
+        if let Some(offset) = &self.translate {
+            matrix = Matrix4::translation(offset) * matrix;
+        }
+        Some(matrix)
+    }
+}
+
+/// Error for scene descriptions, which are well formed, but describe something which can not be
+/// rendered.
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render::RenderSettings, sampler::SamplerKind, tone_mapping::ToneMapper};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn transmission_color_is_reached_after_its_distance() {
+        let color = Color::new(0.9, 0.5, 1.);
+        let coefficient = Absorption::Transmission {
+            color,
+            distance: 2.,
+        }
+        .coefficient()
+        .unwrap();
+        for channel in 0..3 {
+            let transmitted = (-coefficient[channel] * 2.).exp();
+            assert!((transmitted - color[channel]).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn rejects_absorption_amplifying_light() {
+        let invalid = [
+            Absorption::Coefficient(Color::new(0.1, -0.1, 0.)),
+            Absorption::Coefficient(Color::new(f64::INFINITY, 0., 0.)),
+            Absorption::Transmission {
+                color: Color::new(0.5, 1.5, 0.5),
+                distance: 1.,
+            },
+            Absorption::Transmission {
+                color: Color::new(0., 0.5, 0.5),
+                distance: 1.,
+            },
+            Absorption::Transmission {
+                color: Color::new(0.5, 0.5, 0.5),
+                distance: 0.,
+            },
+        ];
+        for absorption in &invalid {
+            let error = absorption.coefficient().err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
         }
+        assert_eq!(Absorption::None.coefficient().unwrap()[0], 0.);
+    }
+
+    #[test]
+    fn rejects_volumes_without_positive_density() {
+        for &density in &[-1., 0., f64::NAN, f64::INFINITY] {
+            let volume = VolumeBuilder {
+                boundary: ShapeBuilder::Sphere {
+                    center: Point::new(0., 0., 0.),
+                    radius: 1.,
+                },
+                density,
+                albedo: Color::ONE,
+                placement: Placement::default(),
+            };
+            let error = volume
+                .build(&mut HashMap::new(), 1., BvhStrategy::Sah)
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_shapes() {
+        let invalid = [
+            ShapeBuilder::AxisBox {
+                min: Point::new(0., 1., 0.),
+                max: Point::new(1., 0., 1.),
+            },
+            ShapeBuilder::AxisBox {
+                min: Point::new(0., 0., 0.),
+                max: Point::new(1., 1., 0.),
+            },
+            ShapeBuilder::Plane {
+                point: Point::new(0., 0., 0.),
+                normal: Vec3::ZERO,
+                extent: None,
+            },
+            ShapeBuilder::Plane {
+                point: Point::new(0., 0., 0.),
+                normal: Vec3::new(0., 1., 0.),
+                extent: Some(-1.),
+            },
+            ShapeBuilder::XyRect {
+                x0: 0.,
+                x1: 0.,
+                y0: 0.,
+                y1: 1.,
+                k: 0.,
+            },
+        ];
+        for shape in &invalid {
+            let error = shape
+                .build(&mut HashMap::new(), 1., BvhStrategy::Sah)
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_display_settings() {
+        let mut scene = crate::random_scenes::spheres(&mut StdRng::seed_from_u64(0), 1.5);
+        scene.display.tone_mapper = ToneMapper::ExtendedReinhard { white: 0. };
+        let error = scene.build().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        scene.display.tone_mapper = ToneMapper::ExtendedReinhard { white: 4. };
+        scene.display.exposure = f64::NAN;
+        let error = scene.build().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        scene.display.exposure = -1.;
+        assert!(scene.build().is_ok());
+    }
+
+    #[test]
+    fn non_uniformly_scaled_lights_still_glow() {
+        let scene: SceneBuilder = serde_json::from_str(
+            r#"{
+                "camera": {
+                    "vertical_field_of_view": 40.0, "aspect_ratio": 1.0,
+                    "lookfrom": [0.0, 0.0, -10.0], "lookat": [0.0, 0.0, 0.0],
+                    "view_up": [0.0, 1.0, 0.0], "distance_to_focus": 10.0, "aperture": 0.0,
+                    "exposure_time": 1.0
+                },
+                "background": "None",
+                "world": [
+                    {
+                        "shape": {"Sphere": {"center": [0.0, 0.0, 0.0], "radius": 1.0}},
+                        "material": {"DiffuseLight": {"emit": [4.0, 4.0, 4.0]}},
+                        "scale": [2.0, 1.0, 1.0]
+                    },
+                    {
+                        "shape": {"XyRect": {"x0": -1.0, "x1": 1.0, "y0": -1.0, "y1": 1.0, "k": 0.0}},
+                        "material": {"DiffuseLight": {"emit": [4.0, 4.0, 4.0]}},
+                        "scale": [1.0, 3.0, 1.0], "translate": [0.0, 0.0, -3.0]
+                    },
+                    {
+                        "shape": {"XyRect": {"x0": -20.0, "x1": 20.0, "y0": -20.0, "y1": 20.0, "k": 5.0}},
+                        "material": {"Diffuse": {"albedo": [0.5, 0.5, 0.5]}}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let scene = scene.build().unwrap();
+        let settings = RenderSettings {
+            image_width: 9,
+            image_height: 9,
+            samples_per_pixel: 1,
+            max_depth: 5,
+            seed: 0,
+            noise_threshold: None,
+            sampler: SamplerKind::Independent,
+            spectral: false,
+            tile_size: 9,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        // Seen directly.
+        let center = scene.render_pixel(4, 4, &mut rng, &settings);
+        assert_eq!(center[0], 4.);
+        // Lighting the wall behind them.
+        let corner = (0..100)
+            .map(|_| scene.render_pixel(0, 0, &mut rng, &settings))
+            .fold(Color::ZERO, |sum, color| sum + color);
+        assert!(corner[0] > 0.);
     }
 }
@@ -1,10 +1,16 @@
 use crate::{
-    persistence::{CameraBuilder, HittableBuilder, SceneBuilder, ShapeBuilder, SurfaceBuilder},
+    bvh::BvhStrategy,
+    material::RefractiveIndex,
+    persistence::{
+        Absorption, BackgroundBuilder, CameraBuilder, HittableBuilder, Placement, SceneBuilder,
+        ShapeBuilder, SurfaceBuilder,
+    },
+    tone_mapping::Display,
     vec3::{Color, Point, Vec3},
 };
 use rand::Rng;
 
-pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
+pub fn spheres(rng: &mut (impl Rng + ?Sized), aspect_ratio: f64) -> SceneBuilder {
     let mut world = Vec::new();
     let ground_material = SurfaceBuilder::Checkered(
         Box::new(SurfaceBuilder::Diffuse {
@@ -21,7 +27,7 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
             radius: 1000.,
         },
         material: ground_material,
-        velocity: None,
+        placement: Placement::default(),
     });
 
     let small_radius = 0.2;
@@ -48,7 +54,8 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
                 } else {
                     (
                         SurfaceBuilder::Dielectric {
-                            refractive_index: 1.5,
+                            refractive_index: RefractiveIndex::Constant(1.5),
+                            absorption: Absorption::None,
                         },
                         None,
                     )
@@ -59,7 +66,10 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
                         radius: small_radius,
                     },
                     material,
-                    velocity,
+                    placement: Placement {
+                        velocity,
+                        ..Placement::default()
+                    },
                 };
                 world.push(little_ball);
             }
@@ -72,9 +82,10 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
             radius: 1.0,
         },
         material: SurfaceBuilder::Dielectric {
-            refractive_index: 1.5,
+            refractive_index: RefractiveIndex::Constant(1.5),
+            absorption: Absorption::None,
         },
-        velocity: None,
+        placement: Placement::default(),
     });
 
     world.push(HittableBuilder {
@@ -85,7 +96,7 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
         material: SurfaceBuilder::Diffuse {
             albedo: Color::new(0.4, 0.2, 0.1),
         },
-        velocity: None,
+        placement: Placement::default(),
     });
 
     world.push(HittableBuilder {
@@ -97,7 +108,7 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
             albedo: Color::new(0.7, 0.6, 0.5),
             fuzziness: 0.,
         },
-        velocity: None,
+        placement: Placement::default(),
     });
 
     let camera = CameraBuilder {
@@ -111,5 +122,12 @@ pub fn spheres(rng: &mut impl Rng, aspect_ratio: f64) -> SceneBuilder {
         exposure_time: 1.,
     };
 
-    SceneBuilder { camera, world }
+    SceneBuilder {
+        camera,
+        world,
+        background: BackgroundBuilder::default(),
+        volumes: Vec::new(),
+        display: Display::default(),
+        bvh: BvhStrategy::default(),
+    }
 }
@@ -0,0 +1,194 @@
+use crate::{
+    estimate::PixelEstimate,
+    sampler::SamplerKind,
+    scene::Scene,
+    tile::{tiles, Framebuffer, Tile},
+};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Everything besides the scene, which determines the rendered image.
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub image_height: u32,
+    /// Upper limit of samples taken for each pixel.
+    pub samples_per_pixel: u32,
+    /// Maximum number of "bounces" calculated for each ray.
+    pub max_depth: u32,
+    /// Rendering the same scene with the same seed and settings produces the exact same image.
+    pub seed: u64,
+    /// Enables adaptive sampling. Pixels stop taking samples once the 95% confidence interval of
+    /// their brightness is smaller than this fraction of the brightness itself.
+    pub noise_threshold: Option<f64>,
+    /// Strategy to distribute the samples within each pixel, on the lens and for each bounce.
+    pub sampler: SamplerKind,
+    /// Traces a single, randomly chosen wavelength for each sample instead of RGB colors. Slower
+    /// to converge, but renders dispersion (e.g. rainbows cast by a prism).
+    pub spectral: bool,
+    /// Edge length in pixels of the square blocks the image is split into. Each block is rendered
+    /// as a whole by one thread. Does not affect the image.
+    pub tile_size: u32,
+}
+
+/// Reported to the progress callback of [`render`] each time a tile is finished.
+#[derive(Clone, Copy)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+}
+
+/// Pixels take at least this many samples before they are checked for convergence. Fewer samples
+/// would underestimate the variance of pixels which only rarely hit a bright light.
+const MIN_ADAPTIVE_SAMPLES: u32 = 16;
+
+/// Adds samples to each pixel of `framebuffer`, until it has taken `settings.samples_per_pixel`
+/// samples (or converged, if a noise threshold is set). Pixels which already hold samples, e.g.
+/// from a checkpoint, only take the missing ones. Tiles are rendered in parallel on the rayon
+/// thread pool.
+///
+/// `on_progress` is called after each finished tile. Once `is_cancelled` returns `true` rendering
+/// stops as soon as possible. Tiles in progress at that moment end up evenly, but only partially
+/// sampled. Tiles which have not been started keep the samples they had before, i.e. none for a
+/// new framebuffer, so they show up black in the image.
+pub fn render(
+    scene: &Scene,
+    settings: &RenderSettings,
+    framebuffer: &Framebuffer,
+    on_progress: impl Fn(Progress) + Sync,
+    is_cancelled: impl Fn() -> bool + Sync,
+) {
+    let tiles = tiles(
+        settings.image_width,
+        settings.image_height,
+        settings.tile_size,
+    );
+    let tiles_done = AtomicUsize::new(0);
+
+    // Each tile is rendered with all its samples by one task and then written into the shared
+    // framebuffer. Memory is bound by the image size, rather than threads times image size.
+    tiles.par_iter().for_each(|tile| {
+        let estimates = render_tile(
+            scene,
+            tile,
+            framebuffer.read_tile(tile),
+            settings,
+            &is_cancelled,
+        );
+        framebuffer.write_tile(tile, &estimates);
+        on_progress(Progress {
+            tiles_done: tiles_done.fetch_add(1, Ordering::SeqCst) + 1,
+            tiles_total: tiles.len(),
+        });
+    });
+}
+
+/// Adds samples to the `estimates` of the pixels within `tile` (in the order of [`Tile::pixels`]).
+/// Each pixel takes `samples_per_pixel` samples, unless it converges earlier according to the
+/// noise threshold. Stops early once `is_cancelled` returns `true`, in which case pixels of this
+/// tile have been sampled less often (or not at all).
+fn render_tile(
+    scene: &Scene,
+    tile: &Tile,
+    mut estimates: Vec<PixelEstimate>,
+    settings: &RenderSettings,
+    is_cancelled: &impl Fn() -> bool,
+) -> Vec<PixelEstimate> {
+    let mut samplers: Vec<_> = tile
+        .pixels()
+        .map(|(column, row)| {
+            settings.sampler.for_pixel(
+                settings.seed,
+                row * settings.image_width + column,
+                settings.samples_per_pixel,
+            )
+        })
+        .collect();
+    let is_done = |estimate: &PixelEstimate| {
+        estimate.count() >= settings.samples_per_pixel
+            || settings.noise_threshold.is_some_and(|threshold| {
+                estimate.count() >= MIN_ADAPTIVE_SAMPLES && estimate.is_converged(threshold)
+            })
+    };
+    // Sample all pixels of the tile once, before taking the next sample, so the tile is evenly
+    // sampled if rendering is cancelled.
+    while !is_cancelled() && !estimates.iter().all(is_done) {
+        for (((column, row), sampler), estimate) in
+            tile.pixels().zip(&mut samplers).zip(&mut estimates)
+        {
+            if is_done(estimate) {
+                continue;
+            }
+            sampler.start_sample(estimate.count());
+            estimate.add(scene.render_pixel(column, row, sampler, settings));
+        }
+    }
+    estimates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random_scenes, vec3::Color};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn render_colors(scene: &Scene, seed: u64, sampler: SamplerKind, tile_size: u32) -> Vec<Color> {
+        let settings = RenderSettings {
+            image_width: 24,
+            image_height: 16,
+            samples_per_pixel: 4,
+            max_depth: 5,
+            seed,
+            noise_threshold: None,
+            sampler,
+            spectral: false,
+            tile_size,
+        };
+        let framebuffer = Framebuffer::new(settings.image_width, settings.image_height);
+        render(scene, &settings, &framebuffer, |_progress| (), || false);
+        framebuffer.colors()
+    }
+
+    fn bits(colors: &[Color]) -> Vec<[u64; 3]> {
+        colors
+            .iter()
+            .map(|color| {
+                [
+                    color.x().to_bits(),
+                    color.y().to_bits(),
+                    color.z().to_bits(),
+                ]
+            })
+            .collect()
+    }
+
+    fn scene() -> Scene {
+        random_scenes::spheres(&mut StdRng::seed_from_u64(1), 1.5)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn same_seed_renders_the_same_image() {
+        let scene = scene();
+        for &sampler in &[
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let first = render_colors(&scene, 42, sampler, 16);
+            // The tile size changes which thread renders a pixel, but not the pixel.
+            let second = render_colors(&scene, 42, sampler, 5);
+            assert_eq!(bits(&first), bits(&second));
+        }
+    }
+
+    #[test]
+    fn different_seeds_render_different_noise() {
+        let scene = scene();
+        let first = render_colors(&scene, 1, SamplerKind::Independent, 16);
+        let second = render_colors(&scene, 2, SamplerKind::Independent, 16);
+        assert_ne!(bits(&first), bits(&second));
+    }
+}
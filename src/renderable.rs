@@ -1,25 +1,52 @@
-use crate::{hittable::Hittable, ray::Ray, vec3::Color};
-use rand::rngs::ThreadRng;
+use crate::{
+    hittable::{Hit, Hittable},
+    ray::Ray,
+    vec3::Color,
+};
+use rand::RngCore;
 
 pub trait Renderable {
+    /// `wavelength` in nanometers, if the ray carries light of a single wavelength (spectral
+    /// rendering).
     fn hit_check(
         &self,
         ray: &Ray,
         t_min: f64,
         t_max: f64,
         time: f64,
-        rng: &mut ThreadRng,
-    ) -> HitCheck;
+        wavelength: Option<f64>,
+        rng: &mut dyn RngCore,
+    ) -> HitCheck<'_>;
+
+    /// `true` if any object is hit between `t_min` and `t_max`. Used for shadow rays.
+    fn is_occluded(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> bool;
 }
 
 /// Possible interactions of a ray with objects in the scene.
-pub enum HitCheck {
+pub enum HitCheck<'a> {
     /// The ray did not hit the object
     Miss,
-    /// The ray hid the object and has been completly absorbed by it.
-    Absorbed,
+    /// The ray hid the object and has been completly absorbed by it. The object may still emit
+    /// light on its own though.
+    Absorbed { emitted: Color, distance: f64 },
     /// The ray hit the object and has been scattered by its surface.
-    Reflected { attenuation: Color, scattered: Ray },
+    Reflected {
+        emitted: Color,
+        /// Ray parameter `t` of the hit.
+        distance: f64,
+        attenuation: Color,
+        scattered: Ray,
+        /// The surface hit. Allows for evaluating scattering into other directions than
+        /// `scattered`.
+        hit: Hit<'a>,
+    },
 }
 
 impl<R> Renderable for R
@@ -32,19 +59,38 @@ where
         t_min: f64,
         t_max: f64,
         time: f64,
-        rng: &mut ThreadRng,
-    ) -> HitCheck {
-        if let Some((_distance, hit)) = self.hit(ray, t_min, t_max, time) {
-            if let Some(scattered) = hit.texture.scatter(rng, &hit.intersection, &ray.direction) {
+        wavelength: Option<f64>,
+        rng: &mut dyn RngCore,
+    ) -> HitCheck<'_> {
+        if let Some((distance, hit)) = self.hit(ray, t_min, t_max, time, rng) {
+            let emitted = hit.texture.emitted(&hit.intersection);
+            if let Some(scattered) =
+                hit.texture
+                    .scatter(rng, &hit.intersection, &ray.direction, wavelength)
+            {
                 HitCheck::Reflected {
+                    emitted,
+                    distance,
                     attenuation: scattered.attenuation,
                     scattered: Ray::new(hit.intersection.point, scattered.direction),
+                    hit,
                 }
             } else {
-                HitCheck::Absorbed
+                HitCheck::Absorbed { emitted, distance }
             }
         } else {
             HitCheck::Miss
         }
     }
+
+    fn is_occluded(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        self.hit(ray, t_min, t_max, time, rng).is_some()
+    }
 }
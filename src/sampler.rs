@@ -0,0 +1,449 @@
+use rand::{rngs::StdRng, Error, Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Generates the sample points of a single pixel. Each sample is a point in a high dimensional unit
+/// cube. The first dimensions are used for the position within the pixel and on the lens, the
+/// following ones for each bounce of the path. Samplers spreading the points of a pixel more evenly
+/// than independent random numbers produce less noise for the same number of samples.
+pub trait Sampler: Send {
+    /// Coordinates in [0, 1) of the dimensions `dimension` and `dimension + 1` of the sample with
+    /// `sample_index`. `dimension` is always even. `rng` is a random stream unique to this sample
+    /// of the pixel.
+    fn get_2d(&mut self, sample_index: u32, dimension: u32, rng: &mut StdRng) -> (f64, f64);
+}
+
+/// Independent uniform random numbers for every dimension. Same as not using a sampler at all.
+pub struct Independent;
+
+impl Sampler for Independent {
+    fn get_2d(&mut self, _sample_index: u32, _dimension: u32, rng: &mut StdRng) -> (f64, f64) {
+        (rng.gen_range(0., 1.), rng.gen_range(0., 1.))
+    }
+}
+
+/// Divides each pair of dimensions into a grid of strata and places each sample randomly within
+/// its own stratum. The order of the strata is shuffled independently for each pair of dimensions.
+pub struct Stratified {
+    /// Decorrelates the permutations of different pixels.
+    pixel_hash: u64,
+    columns: u32,
+    rows: u32,
+}
+
+impl Sampler for Stratified {
+    fn get_2d(&mut self, sample_index: u32, dimension: u32, rng: &mut StdRng) -> (f64, f64) {
+        let strata = self.columns * self.rows;
+        let hash = mix_bits(self.pixel_hash ^ dimension as u64) as u32;
+        let stratum = permutation_element(sample_index % strata, strata, hash);
+        let x = (stratum % self.columns) as f64 + rng.gen_range(0., 1.);
+        let y = (stratum / self.columns) as f64 + rng.gen_range(0., 1.);
+        (x / self.columns as f64, y / self.rows as f64)
+    }
+}
+
+/// Halton sequence using a different prime as base for each dimension. The digits are scrambled
+/// with random permutations, which differ for each pixel, dimension and digit, so neighbouring
+/// pixels do not share the same pattern and high dimensions are less correlated. Dimensions beyond
+/// the available primes fall back to random numbers.
+pub struct Halton {
+    pixel_hash: u64,
+}
+
+impl Halton {
+    const PRIMES: [u32; 32] = [
+        2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+        97, 101, 103, 107, 109, 113, 127, 131,
+    ];
+
+    fn get_1d(&mut self, sample_index: u32, dimension: u32, rng: &mut StdRng) -> f64 {
+        match Self::PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let hash = mix_bits(self.pixel_hash ^ dimension as u64);
+                scrambled_radical_inverse(base, sample_index, hash)
+            }
+            None => rng.gen_range(0., 1.),
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn get_2d(&mut self, sample_index: u32, dimension: u32, rng: &mut StdRng) -> (f64, f64) {
+        (
+            self.get_1d(sample_index, dimension, rng),
+            self.get_1d(sample_index, dimension + 1, rng),
+        )
+    }
+}
+
+/// The first two dimensions of the Sobol sequence for every pair of dimensions ("padded" Sobol).
+/// Samples are shuffled differently for each pair, so the dimensions are not correlated, and each
+/// pair is randomized with Owen scrambling, so pixels do not share the same pattern. Works best if
+/// the number of samples per pixel is a power of two.
+pub struct Sobol {
+    pixel_hash: u64,
+    samples_per_pixel: u32,
+}
+
+impl Sampler for Sobol {
+    fn get_2d(&mut self, sample_index: u32, dimension: u32, _rng: &mut StdRng) -> (f64, f64) {
+        let hash = mix_bits(self.pixel_hash ^ dimension as u64);
+        let index = permutation_element(sample_index, self.samples_per_pixel, hash as u32);
+        let x = owen_scramble(sobol_first(index), (hash >> 32) as u32);
+        let y = owen_scramble(sobol_second(index), mix_bits(hash) as u32);
+        (to_unit_u32(x), to_unit_u32(y))
+    }
+}
+
+/// The kinds of samplers which can be selected on the command line.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    /// `true` if the samples of a pixel are only well distributed (and distinct) for the number of
+    /// samples per pixel the sampler has been created for.
+    pub(crate) fn depends_on_sample_count(self) -> bool {
+        matches!(self, SamplerKind::Stratified | SamplerKind::Sobol)
+    }
+
+    /// Sampler for the pixel with `pixel_index`. Every pixel gets a different random stream derived
+    /// from `seed`, so the result does not depend on which thread renders it.
+    pub(crate) fn for_pixel(
+        self,
+        seed: u64,
+        pixel_index: u32,
+        samples_per_pixel: u32,
+    ) -> PixelSampler {
+        let pixel_hash = mix_bits(seed ^ mix_bits(pixel_index as u64));
+        let sampler: Box<dyn Sampler> = match self {
+            SamplerKind::Independent => Box::new(Independent),
+            SamplerKind::Stratified => {
+                let columns = (samples_per_pixel as f64).sqrt().ceil() as u32;
+                let rows = samples_per_pixel.div_ceil(columns);
+                Box::new(Stratified {
+                    pixel_hash,
+                    columns,
+                    rows,
+                })
+            }
+            SamplerKind::Halton => Box::new(Halton { pixel_hash }),
+            SamplerKind::Sobol => Box::new(Sobol {
+                pixel_hash,
+                samples_per_pixel,
+            }),
+        };
+        PixelSampler::new(sampler, seed, pixel_index)
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" => Ok(SamplerKind::Independent),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!(
+                "Unknown sampler '{}'. Expected one of: independent, stratified, halton, sobol.",
+                s
+            )),
+        }
+    }
+}
+
+/// Hands out the dimensions of the current sample one after another as random numbers. This way
+/// the camera, materials and lights draw from the sampler without knowing about it, using the same
+/// `Rng` interface as for independent random numbers.
+pub struct PixelSampler {
+    sampler: Box<dyn Sampler>,
+    seed: u64,
+    pixel_index: u32,
+    /// Random numbers for the current sample. Reseeded for each sample, so a sample does not
+    /// depend on how many samples have been taken before (e.g. by a previous, resumed render).
+    rng: StdRng,
+    sample_index: u32,
+    /// Next dimension to hand out.
+    dimension: u32,
+    /// Second coordinate of the last 2D sample, handed out next.
+    pending: Option<f64>,
+}
+
+impl PixelSampler {
+    fn new(sampler: Box<dyn Sampler>, seed: u64, pixel_index: u32) -> Self {
+        Self {
+            sampler,
+            seed,
+            pixel_index,
+            rng: sample_rng(seed, pixel_index, 0),
+            sample_index: 0,
+            dimension: 0,
+            pending: None,
+        }
+    }
+
+    /// Must be called before rendering each sample of the pixel.
+    pub fn start_sample(&mut self, sample_index: u32) {
+        self.sample_index = sample_index;
+        self.rng = sample_rng(self.seed, self.pixel_index, sample_index);
+        self.dimension = 0;
+        self.pending = None;
+    }
+
+    fn next_1d(&mut self) -> f64 {
+        let value = match self.pending.take() {
+            Some(value) => value,
+            None => {
+                let (x, y) = self
+                    .sampler
+                    .get_2d(self.sample_index, self.dimension, &mut self.rng);
+                self.pending = Some(y);
+                x
+            }
+        };
+        self.dimension += 1;
+        value
+    }
+}
+
+impl RngCore for PixelSampler {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_1d() * (1u64 << 32) as f64) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // Floats are generated from the upper bits, so the value survives the round trip.
+        (self.next_1d() * 2f64.powi(64)) as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Independent random number stream for each sample of each pixel.
+fn sample_rng(seed: u64, pixel_index: u32, sample_index: u32) -> StdRng {
+    let mut key = [0u8; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+    key[8..12].copy_from_slice(&pixel_index.to_le_bytes());
+    key[12..16].copy_from_slice(&sample_index.to_le_bytes());
+    StdRng::from_seed(key)
+}
+
+/// Digits of `index` in `base`, mirrored at the decimal point. Each digit (including the infinite
+/// leading zeros of `index`) is replaced using a random permutation chosen by `hash` and its
+/// position.
+fn scrambled_radical_inverse(base: u32, mut index: u32, hash: u64) -> f64 {
+    let inverse_base = 1. / base as f64;
+    let mut factor = inverse_base;
+    let mut result = 0.;
+    let mut position = 0;
+    // Stop once further digits are below the precision of the result.
+    while factor > f64::EPSILON {
+        let seed = mix_bits(hash ^ position) as u32;
+        let digit = permutation_element(index % base, base, seed);
+        result += digit as f64 * factor;
+        index /= base;
+        factor *= inverse_base;
+        position += 1;
+    }
+    result.min(1. - f64::EPSILON / 2.)
+}
+
+/// First dimension of the Sobol sequence (van der Corput sequence) as fixed point fraction.
+fn sobol_first(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// Second dimension of the Sobol sequence as fixed point fraction.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut result = 0;
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// Fast approximation of Owen scrambling by Laine and Karras: each bit is flipped depending on the
+/// bits above it, which randomizes the points while keeping their stratification.
+fn owen_scramble(mut value: u32, seed: u32) -> u32 {
+    value = value.reverse_bits();
+    value ^= value.wrapping_mul(0x3d20_adea);
+    value = value.wrapping_add(seed);
+    value = value.wrapping_mul((seed >> 16) | 1);
+    value ^= value.wrapping_mul(0x0552_6c56);
+    value ^= value.wrapping_mul(0x53a2_2864);
+    value.reverse_bits()
+}
+
+/// Element `index` of a random permutation of `0..length` chosen by `seed`, without storing the
+/// permutation (Kensler, "Correlated Multi-Jittered Sampling").
+fn permutation_element(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length.wrapping_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            return (index.wrapping_add(seed)) % length;
+        }
+    }
+}
+
+/// Scrambles the bits of `value` (MurmurHash3 finalizer).
+fn mix_bits(mut value: u64) -> u64 {
+    value ^= value >> 31;
+    value = value.wrapping_mul(0x7fb5_d329_728e_a185);
+    value ^= value >> 27;
+    value = value.wrapping_mul(0x81da_def4_bc2d_d44d);
+    value ^= value >> 33;
+    value
+}
+
+fn to_unit_u32(value: u32) -> f64 {
+    value as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first two dimensions of all samples of one pixel.
+    fn points(kind: SamplerKind, samples_per_pixel: u32) -> Vec<(f64, f64)> {
+        let mut sampler = kind.for_pixel(7, 123, samples_per_pixel);
+        (0..samples_per_pixel)
+            .map(|sample_index| {
+                sampler.start_sample(sample_index);
+                let x = sampler.gen_range(0., 1.);
+                let y = sampler.gen_range(0., 1.);
+                (x, y)
+            })
+            .collect()
+    }
+
+    /// Whether each cell of a `columns` × `rows` grid contains exactly one point.
+    fn is_stratified(points: &[(f64, f64)], columns: u32, rows: u32) -> bool {
+        let mut counts = vec![0; (columns * rows) as usize];
+        for &(x, y) in points {
+            let column = (x * columns as f64) as u32;
+            let row = (y * rows as f64) as u32;
+            counts[(row * columns + column) as usize] += 1;
+        }
+        counts.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn permutation_element_is_a_permutation() {
+        for &length in &[1, 2, 5, 16, 100] {
+            for &seed in &[0, 1, 0xdead_beef] {
+                let mut elements: Vec<_> = (0..length)
+                    .map(|index| permutation_element(index, length, seed))
+                    .collect();
+                elements.sort_unstable();
+                assert!(elements.into_iter().eq(0..length));
+            }
+        }
+    }
+
+    #[test]
+    fn samples_lie_within_the_unit_square() {
+        for &kind in &[
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let mut sampler = kind.for_pixel(3, 5, 64);
+            for sample_index in 0..64 {
+                sampler.start_sample(sample_index);
+                // Beyond the primes of the Halton sampler.
+                for _ in 0..40 {
+                    let value = sampler.next_1d();
+                    assert!((0. ..1.).contains(&value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_places_one_sample_in_each_stratum() {
+        assert!(is_stratified(&points(SamplerKind::Stratified, 16), 4, 4));
+    }
+
+    #[test]
+    fn sobol_places_one_sample_in_each_elementary_interval() {
+        let points = points(SamplerKind::Sobol, 16);
+        for &(columns, rows) in &[(16, 1), (8, 2), (4, 4), (2, 8), (1, 16)] {
+            assert!(is_stratified(&points, columns, rows));
+        }
+    }
+
+    #[test]
+    fn halton_first_dimension_is_stratified() {
+        let points: Vec<_> = points(SamplerKind::Halton, 8)
+            .into_iter()
+            .map(|(x, _)| (x, 0.))
+            .collect();
+        assert!(is_stratified(&points, 8, 1));
+    }
+
+    #[test]
+    fn samples_do_not_depend_on_earlier_samples() {
+        let mut sampler = SamplerKind::Halton.for_pixel(9, 0, 16);
+        sampler.start_sample(5);
+        let first: Vec<_> = (0..40).map(|_| sampler.next_1d()).collect();
+        sampler.start_sample(2);
+        sampler.next_1d();
+        sampler.start_sample(5);
+        let second: Vec<_> = (0..40).map(|_| sampler.next_1d()).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn sobol_second_dimension_matches_the_reference() {
+        // Direction numbers 1/2, 3/4, 5/8 combined by the bits of the index (not in Gray code order).
+        let values: Vec<_> = (0..8)
+            .map(|index| to_unit_u32(sobol_second(index)))
+            .collect();
+        assert_eq!(values, [0., 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875]);
+    }
+}
@@ -1,84 +1,278 @@
 use crate::{
+    background::Background,
     camera::Camera,
+    hittable::Hit,
+    lights::Lights,
     ray::Ray,
+    render::RenderSettings,
     renderable::{HitCheck, Renderable},
-    vec3::{Color, Vec3},
+    spectrum::{rgb_to_spectral, sample_wavelength, wavelength_pdf, wavelength_to_rgb},
+    vec3::Color,
 };
-use rand::{rngs::ThreadRng, Rng};
+use rand::{Rng, RngCore};
 
 pub struct Scene {
-    pub world: Box<dyn Renderable + Sync + Send>,
-    pub camera: Camera,
+    pub(crate) world: Box<dyn Renderable + Sync + Send>,
+    pub(crate) camera: Camera,
+    pub(crate) background: Background,
+    /// Glowing objects, which are sampled explicitly. They are also part of `world`.
+    pub(crate) lights: Lights,
 }
 
 impl Scene {
-    pub fn new(world: Box<dyn Renderable + Sync + Send>, camera: Camera) -> Self {
-        Self { world, camera }
+    pub(crate) fn new(
+        world: Box<dyn Renderable + Sync + Send>,
+        camera: Camera,
+        background: Background,
+        lights: Lights,
+    ) -> Self {
+        Self {
+            world,
+            camera,
+            background,
+            lights,
+        }
     }
 
-    pub fn render_sample(
+    /// Color of a single sample for the pixel in `column` and `row`. Rows are counted from the top
+    /// of the image.
+    pub fn render_pixel(
         &self,
-        rng: &mut ThreadRng,
-        max_depth: u32,
-        image_height: u32,
-        image_width: u32,
-    ) -> Vec<Color> {
-        let pixels = (0..image_height)
-            .rev()
-            .flat_map(|j| (0..image_width).map(move |i| (i, j)));
-        pixels
-            .map(|(i, j)| {
-                let u = (i as f64 + rng.gen_range(0., 1.)) / (image_width - 1) as f64;
-                let v = (j as f64 + rng.gen_range(0., 1.)) / (image_height - 1) as f64;
-                let ray = self.camera.get_ray(u, v, rng);
-                let time = self.camera.get_time(rng);
-                ray_color(ray, time, self.world.as_ref(), rng, max_depth)
-            })
-            .collect()
+        column: u32,
+        row: u32,
+        rng: &mut dyn RngCore,
+        settings: &RenderSettings,
+    ) -> Color {
+        let RenderSettings {
+            image_width,
+            image_height,
+            max_depth,
+            spectral,
+            ..
+        } = *settings;
+        // The camera expects `v` to grow upwards.
+        let j = image_height - 1 - row;
+        let u = (column as f64 + rng.gen_range(0., 1.)) / (image_width - 1) as f64;
+        let v = (j as f64 + rng.gen_range(0., 1.)) / (image_height - 1) as f64;
+        let ray = self.camera.get_ray(u, v, rng);
+        let time = self.camera.get_time(rng);
+        if spectral {
+            let wavelength = sample_wavelength(rng.gen_range(0., 1.));
+            // All channels hold the radiance at `wavelength`.
+            let radiance = ray_color(ray, time, Some(wavelength), self, rng, max_depth)[0];
+            wavelength_to_rgb(wavelength) * (radiance / wavelength_pdf(wavelength))
+        } else {
+            ray_color(ray, time, None, self, rng, max_depth)
+        }
     }
 }
 
+/// Light arriving along `ray`. If a `wavelength` is given, only light of this wavelength is traced
+/// and the result holds its radiance in all three channels.
 fn ray_color(
     mut ray: Ray,
     time: f64,
-    world: &dyn Renderable,
-    rng: &mut ThreadRng,
+    wavelength: Option<f64>,
+    scene: &Scene,
+    rng: &mut dyn RngCore,
     depth: u32,
 ) -> Color {
-    let mut trace = |ray| {
-        match world.hit_check(&ray, 0.001, f64::INFINITY, time, rng) {
+    let Scene {
+        world,
+        background,
+        lights,
+        ..
+    } = scene;
+
+    // Light gathered along the path so far.
+    let mut color = Color::ZERO;
+    // Fraction of light, which makes it from the current ray origin to the camera.
+    let mut throughput = Color::ONE;
+    // Probability density with which the direction of the current ray has been chosen. `None` for
+    // rays from the camera and for discrete scattering (mirrors, glass), which can not be the
+    // result of sampling a light.
+    let mut direction_pdf = None;
+    for _ in 0..depth {
+        match world.hit_check(&ray, 0.001, f64::INFINITY, time, wavelength, rng) {
             // No object in the scene has been hit. Let's use the ambient light.
-            HitCheck::Miss => (ambient(&ray.direction), None),
-            HitCheck::Absorbed => (Color::new(0., 0., 0.), None),
+            HitCheck::Miss => {
+                color += &throughput * &at_wavelength(background.color(&ray.direction), wavelength);
+                return color;
+            }
+            HitCheck::Absorbed { emitted, distance } => {
+                let weight = emission_weight(direction_pdf, lights, &ray, distance, time);
+                color += &throughput * &(at_wavelength(emitted, wavelength) * weight);
+                return color;
+            }
             HitCheck::Reflected {
+                emitted,
+                distance,
                 attenuation,
                 scattered,
-            } => (attenuation, Some(scattered)),
+                hit,
+            } => {
+                // Leaving an object through its back face means the ray has travelled through its
+                // inside, since it has been refracted into it. Light found at this point, be it
+                // emitted or sampled, has been absorbed along the way.
+                if !hit.intersection.front_face {
+                    let length = distance * ray.direction.length();
+                    let transmittance = hit.texture.transmittance(&hit.intersection, length);
+                    throughput *= at_wavelength(transmittance, wavelength);
+                }
+                let weight = emission_weight(direction_pdf, lights, &ray, distance, time);
+                color += &throughput * &(at_wavelength(emitted, wavelength) * weight);
+                color += &throughput * &sample_lights(&hit, &ray, scene, time, wavelength, rng);
+                let (attenuation, scattered, pdf) = choose_direction(
+                    &hit,
+                    &ray,
+                    attenuation,
+                    scattered,
+                    background,
+                    wavelength,
+                    rng,
+                );
+                throughput *= at_wavelength(attenuation, wavelength);
+                direction_pdf = pdf;
+                ray = scattered;
+            }
         }
+    }
+    color
+}
+
+/// In spectral rendering, the value of `color` at `wavelength` in all three channels. Otherwise
+/// `color` itself.
+fn at_wavelength(color: Color, wavelength: Option<f64>) -> Color {
+    match wavelength {
+        Some(wavelength) => Color::ONE * rgb_to_spectral(&color, wavelength),
+        None => color,
+    }
+}
+
+/// Weight of light emitted by a surface found by following `ray`. The same light could also have
+/// been found by sampling the light sources directly. Both strategies are combined using multiple
+/// importance sampling, so each one is weighted by how likely it is to find this light. Glowing
+/// objects which are not part of `lights` can only be found by following the path.
+fn emission_weight(
+    direction_pdf: Option<f64>,
+    lights: &Lights,
+    ray: &Ray,
+    distance: f64,
+    time: f64,
+) -> f64 {
+    match direction_pdf {
+        Some(pdf) => match lights.pdf(ray, distance, time) {
+            light_pdf if light_pdf > 0. => power_heuristic(pdf, light_pdf),
+            _ => 1.,
+        },
+        None => 1.,
+    }
+}
+
+/// Next event estimation: Light arriving directly from a randomly chosen light source, which is
+/// scattered into the opposite of `incoming` by the surface hit.
+fn sample_lights(
+    hit: &Hit,
+    incoming: &Ray,
+    scene: &Scene,
+    time: f64,
+    wavelength: Option<f64>,
+    rng: &mut dyn RngCore,
+) -> Color {
+    let origin = hit.intersection.point;
+    let (to_light, emit, light_pdf) = match scene.lights.sample(&origin, time, rng) {
+        Some(sample) => sample,
+        None => return Color::ZERO,
+    };
+    let (value, surface_pdf) = match hit.texture.evaluate(
+        &hit.intersection,
+        &incoming.direction,
+        &to_light,
+        wavelength,
+    ) {
+        Some(evaluation) => evaluation,
+        // Mirrors and glass only scatter into one direction, which a light sample never hits.
+        None => return Color::ZERO,
+    };
+    if value.iter().all(|&component| component == 0.) {
+        return Color::ZERO;
+    }
+    let distance = to_light.length();
+    let shadow_ray = Ray::new(origin, to_light / distance);
+    // Stop a bit short of the light, so it does not occlude itself.
+    if scene
+        .world
+        .is_occluded(&shadow_ray, 0.001, distance - 0.001, time, rng)
+    {
+        return Color::ZERO;
+    }
+    // Must match the density `choose_direction` uses for the same direction.
+    let scatter_pdf = if scene.background.is_importance_sampled() {
+        0.5 * surface_pdf + 0.5 * scene.background.pdf(&to_light)
+    } else {
+        surface_pdf
     };
+    let weight = power_heuristic(light_pdf, scatter_pdf);
+    &at_wavelength(value, wavelength) * &at_wavelength(emit, wavelength) * (weight / light_pdf)
+}
 
-    let mut color = Color::new(1., 1., 1.);
-    for _ in 0..depth {
-        let (attenuation, scattered) = trace(ray);
-        color *= attenuation;
-        if let Some(next_ray) = scattered {
-            ray = next_ray
-        } else {
-            break;
-        }
+/// Weight for a sample taken with the strategy of density `pdf`, if the same sample could also
+/// have been taken by a strategy with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let sum = pdf_squared + other_pdf * other_pdf;
+    if sum > 0. {
+        pdf_squared / sum
+    } else {
+        0.
     }
-    // In case of us hitting the max depth limit the original code proposed by the rtiow to return
-    // black, I find it however more appealing to return the attenuated color so far. For
-    // increasingly higher depth limits both variants become similar anyway.
-    color
 }
 
-fn ambient(direction: &Vec3) -> Color {
-    // y is between -1 and 1
-    let y = direction.unit().y();
-    // 0 <= t <= 1
-    let t = (y + 1.) / 2.;
-    let blend_start = Color::new(1., 1., 1.);
-    let blend_end = Color::new(0.5, 0.7, 1.0);
-    blend_start * (1. - t) + blend_end * t
+/// Direction to continue the path into, together with the attenuation along it and the
+/// probability density it has been chosen with (`None` for discrete scattering like mirrors and
+/// glass).
+///
+/// If the background is importance sampled, this chooses randomly between the direction the
+/// surface scattered into and a direction picked proportional to the brightness of the background.
+/// Attenuation is weighted with the combined probability density of both strategies. This way
+/// small bright spots of an environment map (e.g. the sun) are found far more often than by
+/// scattering alone.
+fn choose_direction(
+    hit: &Hit,
+    incoming: &Ray,
+    attenuation: Color,
+    scattered: Ray,
+    background: &Background,
+    wavelength: Option<f64>,
+    rng: &mut dyn RngCore,
+) -> (Color, Ray, Option<f64>) {
+    let evaluate = |direction| {
+        hit.texture.evaluate(
+            &hit.intersection,
+            &incoming.direction,
+            direction,
+            wavelength,
+        )
+    };
+    let surface_pdf = match evaluate(&scattered.direction) {
+        Some((_, pdf)) => pdf,
+        // Surfaces like mirrors or glass only scatter into one direction. Nothing to guide here.
+        None => return (attenuation, scattered, None),
+    };
+    if !background.is_importance_sampled() {
+        return (attenuation, scattered, Some(surface_pdf));
+    }
+    let direction = if rng.gen_bool(0.5) {
+        scattered.direction
+    } else {
+        background.sample_direction(rng)
+    };
+    let (value, surface_pdf) = evaluate(&direction).unwrap();
+    let pdf = 0.5 * surface_pdf + 0.5 * background.pdf(&direction);
+    let attenuation = if pdf > 0. { value / pdf } else { Color::ZERO };
+    (
+        attenuation,
+        Ray::new(scattered.origin, direction),
+        Some(pdf),
+    )
 }
@@ -1,14 +1,83 @@
 use crate::{
     bounding_box::{Aabb, BoundingBox},
     ray::Ray,
-    vec3::{dot, Point, Vec3},
+    vec3::{cross, dot, orthonormal_basis, Point, Vec3},
 };
+use rand::{Rng, RngCore};
+use std::{f64::consts::PI, sync::Arc};
 
 // A physical volume (without any material associated yet).
 pub trait Shape {
     fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)>;
+
+    /// Picks a random point on the surface, which is (potentially) visible from `origin`. Used to
+    /// send rays towards light sources. Returns the point together with the probability density
+    /// of choosing the direction towards it, with respect to solid angle as seen from `origin`.
+    ///
+    /// `None` if the shape does not support sampling.
+    fn sample_towards(&self, _origin: &Point, _rng: &mut dyn RngCore) -> Option<(Point, f64)> {
+        None
+    }
+
+    /// Probability density of `sample_towards` choosing the direction of `ray`, if `ray.origin` is
+    /// passed as origin. Zero if the ray does not hit the shape before `t_max`.
+    fn pdf_towards(&self, _ray: &Ray, _t_max: f64) -> f64 {
+        0.
+    }
+}
+
+impl<S> Shape for Box<S>
+where
+    S: Shape + ?Sized,
+{
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)> {
+        self.as_ref().intersect(ray, t_min, t_max)
+    }
+
+    fn sample_towards(&self, origin: &Point, rng: &mut dyn RngCore) -> Option<(Point, f64)> {
+        self.as_ref().sample_towards(origin, rng)
+    }
+
+    fn pdf_towards(&self, ray: &Ray, t_max: f64) -> f64 {
+        self.as_ref().pdf_towards(ray, t_max)
+    }
+}
+
+/// Allows shapes to be shared, e.g. between the hierarchy of a mesh and the lights it is made of.
+impl<S> Shape for Arc<S>
+where
+    S: Shape + ?Sized,
+{
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)> {
+        self.as_ref().intersect(ray, t_min, t_max)
+    }
+
+    fn sample_towards(&self, origin: &Point, rng: &mut dyn RngCore) -> Option<(Point, f64)> {
+        self.as_ref().sample_towards(origin, rng)
+    }
+
+    fn pdf_towards(&self, ray: &Ray, t_max: f64) -> f64 {
+        self.as_ref().pdf_towards(ray, t_max)
+    }
 }
 
+/// Converts a probability density with respect to area on a surface into one with respect to
+/// solid angle as seen from `origin`.
+fn area_to_solid_angle_pdf(area_pdf: f64, origin: &Point, point: &Point, normal: &Vec3) -> f64 {
+    let to_point = *point - *origin;
+    let distance_squared = to_point.length_squared();
+    let cosine = dot(*normal, to_point).abs() / distance_squared.sqrt();
+    if cosine <= 0. {
+        0.
+    } else {
+        area_pdf * distance_squared / cosine
+    }
+}
+
+/// A shape which can be placed in a bounding volume hierarchy.
+pub trait BoundedShape: Shape + BoundingBox + Send + Sync {}
+impl<T> BoundedShape for T where T: Shape + BoundingBox + Send + Sync {}
+
 /// Describes the point there the Ray punctures the shape. The mathematical ray that is. The
 /// physical light is much more likely to be reflected of course.
 pub struct Puncture {
@@ -19,6 +88,8 @@ pub struct Puncture {
     pub front_face: bool,
     /// Always pointing against the intersecting ray.
     pub normal: Vec3,
+    /// Position on the surface of the shape, used to look up image textures. Both coordinates are
+    /// usually within [0, 1]. `(0, 0)` corresponds to the bottom left of an image.
     pub texture_coordiantes: (f64, f64),
 }
 
@@ -86,13 +157,62 @@ impl Shape for Sphere {
         t.map(|t| {
             let point = ray.at(t);
             let outward_normal = (point - self.center) / self.radius;
-
-            (
-                t,
-                Puncture::from_outward_normal(point, outward_normal, &ray.direction),
-            )
+            let mut puncture = Puncture::from_outward_normal(point, outward_normal, &ray.direction);
+            puncture.texture_coordiantes = sphere_texture_coordinates(&outward_normal);
+            (t, puncture)
         })
     }
+
+    fn sample_towards(&self, origin: &Point, rng: &mut dyn RngCore) -> Option<(Point, f64)> {
+        // Sample uniformly within the cone of directions from origin, which hit the sphere.
+        let to_center = self.center - *origin;
+        let cos_theta_max = self.cos_theta_max(origin)?;
+        let z = 1. + rng.gen_range(0., 1.) * (cos_theta_max - 1.);
+        let phi: f64 = rng.gen_range(0., 2. * PI);
+        let r = (1. - z * z).max(0.).sqrt();
+        let w = to_center.unit();
+        let (u, v) = orthonormal_basis(&w);
+        let direction = u * (r * phi.cos()) + v * (r * phi.sin()) + w * z;
+        let (_, puncture) = self.intersect(&Ray::new(*origin, direction), 0., f64::INFINITY)?;
+        Some((puncture.point, uniform_cone_pdf(cos_theta_max)))
+    }
+
+    fn pdf_towards(&self, ray: &Ray, t_max: f64) -> f64 {
+        match (
+            self.cos_theta_max(&ray.origin),
+            self.intersect(ray, 0., t_max),
+        ) {
+            (Some(cos_theta_max), Some(_)) => uniform_cone_pdf(cos_theta_max),
+            _ => 0.,
+        }
+    }
+}
+
+impl Sphere {
+    /// Cosine of the half opening angle of the cone of directions from `origin` hitting the
+    /// sphere. `None` if `origin` is inside of the sphere.
+    fn cos_theta_max(&self, origin: &Point) -> Option<f64> {
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            None
+        } else {
+            Some((1. - radius_squared / distance_squared).sqrt())
+        }
+    }
+}
+
+fn uniform_cone_pdf(cos_theta_max: f64) -> f64 {
+    1. / (2. * PI * (1. - cos_theta_max))
+}
+
+/// Maps a point on the unit sphere to texture coordinates. `u` goes around the vertical axis
+/// starting at `-x` and `v` goes from the bottom (`-y`) to the top (`+y`). This fits the usual
+/// layout of equirectangular images like world maps.
+fn sphere_texture_coordinates(point: &Point) -> (f64, f64) {
+    let theta = (-point.y()).clamp(-1., 1.).acos();
+    let phi = (-point.z()).atan2(point.x()) + PI;
+    (phi / (2. * PI), theta / PI)
 }
 
 impl BoundingBox for Sphere {
@@ -101,3 +221,366 @@ impl BoundingBox for Sphere {
         Aabb::new(self.center - r3, self.center + r3)
     }
 }
+
+/// A flat triangle, e.g. as part of a mesh loaded from an OBJ file.
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Point; 3],
+    /// Per vertex normals. Interpolated over the surface to make meshes appear smooth. If absent,
+    /// the face normal is used.
+    normals: Option<[Vec3; 3]>,
+    texture_coordinates: [(f64, f64); 3],
+}
+
+impl Triangle {
+    /// The outward side of the triangle is the one from which the vertices appear in counter
+    /// clockwise order. If no texture coordinates are given, the triangle is mapped to the lower
+    /// left half of the unit square.
+    pub fn new(
+        vertices: [Point; 3],
+        normals: Option<[Vec3; 3]>,
+        texture_coordinates: Option<[(f64, f64); 3]>,
+    ) -> Self {
+        Self {
+            vertices,
+            normals,
+            texture_coordinates: texture_coordinates.unwrap_or([(0., 0.), (1., 0.), (0., 1.)]),
+        }
+    }
+}
+
+impl Shape for Triangle {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)> {
+        // Möller-Trumbore intersection. We solve O + tD = (1 - u - v) V0 + u V1 + v V2 for t, u and
+        // v using Cramer's rule.
+        let [v0, v1, v2] = self.vertices;
+        let edge_1 = v1 - v0;
+        let edge_2 = v2 - v0;
+        let p = cross(&ray.direction, &edge_2);
+        let determinant = dot(edge_1, p);
+        if determinant.abs() < 1e-12 {
+            // Ray is parallel to the plane of the triangle.
+            return None;
+        }
+        let inv_determinant = determinant.recip();
+        let s = ray.origin - v0;
+        let u = dot(s, p) * inv_determinant;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+        let q = cross(&s, &edge_1);
+        let v = dot(ray.direction, q) * inv_determinant;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+        let t = dot(edge_2, q) * inv_determinant;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let w = 1. - u - v;
+        let outward_normal = if let Some([n0, n1, n2]) = self.normals {
+            (n0 * w + n1 * u + n2 * v).unit()
+        } else {
+            cross(&edge_1, &edge_2).unit()
+        };
+        let [(u0, v0), (u1, v1), (u2, v2)] = self.texture_coordinates;
+        let mut puncture = Puncture::from_outward_normal(ray.at(t), outward_normal, &ray.direction);
+        puncture.texture_coordiantes = (u0 * w + u1 * u + u2 * v, v0 * w + v1 * u + v2 * v);
+        Some((t, puncture))
+    }
+
+    fn sample_towards(&self, origin: &Point, rng: &mut dyn RngCore) -> Option<(Point, f64)> {
+        // Uniformly distributed barycentric coordinates.
+        let sqrt_r1 = rng.gen_range(0., 1_f64).sqrt();
+        let r2 = rng.gen_range(0., 1.);
+        let [v0, v1, v2] = self.vertices;
+        let point = v0 * (1. - sqrt_r1) + v1 * (sqrt_r1 * (1. - r2)) + v2 * (sqrt_r1 * r2);
+        let (normal, area) = self.normal_and_area();
+        Some((
+            point,
+            area_to_solid_angle_pdf(area.recip(), origin, &point, &normal),
+        ))
+    }
+
+    fn pdf_towards(&self, ray: &Ray, t_max: f64) -> f64 {
+        self.intersect(ray, 0., t_max)
+            .map(|(_, puncture)| {
+                let (normal, area) = self.normal_and_area();
+                area_to_solid_angle_pdf(area.recip(), &ray.origin, &puncture.point, &normal)
+            })
+            .unwrap_or(0.)
+    }
+}
+
+impl Triangle {
+    /// Geometric normal (ignoring per vertex normals) and surface area.
+    fn normal_and_area(&self) -> (Vec3, f64) {
+        let [v0, v1, v2] = self.vertices;
+        let normal = cross(&(v1 - v0), &(v2 - v0));
+        let length = normal.length();
+        (normal / length, length / 2.)
+    }
+}
+
+impl BoundingBox for Triangle {
+    fn bounding_box(&self, _exposure_time: f64) -> Aabb {
+        // Pad the box a little bit, so triangles parallel to an axis do not end up with a box of
+        // zero thickness, which could not be hit by any ray.
+        let padding = 1e-4;
+        let mut min = self.vertices[0];
+        let mut max = self.vertices[0];
+        for vertex in &self.vertices[1..] {
+            for d in 0..3 {
+                min[d] = min[d].min(vertex[d]);
+                max[d] = max[d].max(vertex[d]);
+            }
+        }
+        let padding = Vec3::new(padding, padding, padding);
+        Aabb::new(min - padding, max + padding)
+    }
+}
+
+/// Rectangle perpendicular to one of the coordinate axes.
+pub struct AxisRect {
+    /// Index of the first axis within the plane of the rectangle. Texture coordinate `u` runs
+    /// along it.
+    a: usize,
+    /// Index of the second axis within the plane of the rectangle. Texture coordinate `v` runs
+    /// along it.
+    b: usize,
+    /// Index of the axis perpendicular to the rectangle.
+    normal_axis: usize,
+    a_range: (f64, f64),
+    b_range: (f64, f64),
+    /// Position of the rectangle along the normal axis.
+    k: f64,
+    /// `1.` if the outward normal points along the normal axis, `-1.` if it points the opposite way.
+    orientation: f64,
+}
+
+impl AxisRect {
+    /// Rectangle at `z = k` with its outward normal pointing towards `+z`.
+    pub fn xy(x: (f64, f64), y: (f64, f64), k: f64) -> Self {
+        Self::new(0, 1, x, y, k)
+    }
+
+    /// Rectangle at `y = k` with its outward normal pointing towards `+y`.
+    pub fn xz(x: (f64, f64), z: (f64, f64), k: f64) -> Self {
+        Self::new(0, 2, x, z, k)
+    }
+
+    /// Rectangle at `x = k` with its outward normal pointing towards `+x`.
+    pub fn yz(y: (f64, f64), z: (f64, f64), k: f64) -> Self {
+        Self::new(1, 2, y, z, k)
+    }
+
+    /// Same rectangle, but with the outward normal pointing the other way.
+    pub fn flipped(mut self) -> Self {
+        self.orientation = -self.orientation;
+        self
+    }
+
+    /// The bounds of each range may be given in either order.
+    fn new(a: usize, b: usize, a_range: (f64, f64), b_range: (f64, f64), k: f64) -> Self {
+        let sorted = |(start, end): (f64, f64)| (start.min(end), start.max(end));
+        Self {
+            a,
+            b,
+            normal_axis: 3 - a - b,
+            a_range: sorted(a_range),
+            b_range: sorted(b_range),
+            k,
+            orientation: 1.,
+        }
+    }
+}
+
+impl Shape for AxisRect {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)> {
+        let n = self.normal_axis;
+        if ray.direction[n] == 0. {
+            // Ray is parallel to the rectangle.
+            return None;
+        }
+        let t = (self.k - ray.origin[n]) / ray.direction[n];
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let point = ray.at(t);
+        let (a0, a1) = self.a_range;
+        let (b0, b1) = self.b_range;
+        let (a, b) = (point[self.a], point[self.b]);
+        if a < a0 || a > a1 || b < b0 || b > b1 {
+            return None;
+        }
+        let mut outward_normal = Vec3::ZERO;
+        outward_normal[n] = self.orientation;
+        let mut puncture = Puncture::from_outward_normal(point, outward_normal, &ray.direction);
+        puncture.texture_coordiantes = ((a - a0) / (a1 - a0), (b - b0) / (b1 - b0));
+        Some((t, puncture))
+    }
+
+    fn sample_towards(&self, origin: &Point, rng: &mut dyn RngCore) -> Option<(Point, f64)> {
+        if self.area() <= 0. {
+            return None;
+        }
+        let mut point = Point::ZERO;
+        point[self.a] = rng.gen_range(self.a_range.0, self.a_range.1);
+        point[self.b] = rng.gen_range(self.b_range.0, self.b_range.1);
+        point[self.normal_axis] = self.k;
+        let pdf = area_to_solid_angle_pdf(self.area().recip(), origin, &point, &self.normal());
+        Some((point, pdf))
+    }
+
+    fn pdf_towards(&self, ray: &Ray, t_max: f64) -> f64 {
+        self.intersect(ray, 0., t_max)
+            .map(|(_, puncture)| {
+                area_to_solid_angle_pdf(
+                    self.area().recip(),
+                    &ray.origin,
+                    &puncture.point,
+                    &self.normal(),
+                )
+            })
+            .unwrap_or(0.)
+    }
+}
+
+impl AxisRect {
+    fn area(&self) -> f64 {
+        (self.a_range.1 - self.a_range.0) * (self.b_range.1 - self.b_range.0)
+    }
+
+    fn normal(&self) -> Vec3 {
+        let mut normal = Vec3::ZERO;
+        normal[self.normal_axis] = 1.;
+        normal
+    }
+}
+
+impl BoundingBox for AxisRect {
+    fn bounding_box(&self, _exposure_time: f64) -> Aabb {
+        // The box must have a non zero width along the normal axis, otherwise it could not be hit.
+        let padding = 1e-4;
+        let mut min = Point::ZERO;
+        let mut max = Point::ZERO;
+        min[self.a] = self.a_range.0;
+        max[self.a] = self.a_range.1;
+        min[self.b] = self.b_range.0;
+        max[self.b] = self.b_range.1;
+        min[self.normal_axis] = self.k - padding;
+        max[self.normal_axis] = self.k + padding;
+        Aabb::new(min, max)
+    }
+}
+
+/// Axis aligned box made up of six rectangles.
+pub struct AxisBox {
+    min: Point,
+    max: Point,
+    sides: [AxisRect; 6],
+}
+
+impl AxisBox {
+    pub fn new(min: Point, max: Point) -> Self {
+        let x = (min.x(), max.x());
+        let y = (min.y(), max.y());
+        let z = (min.z(), max.z());
+        let sides = [
+            AxisRect::xy(x, y, max.z()),
+            AxisRect::xy(x, y, min.z()).flipped(),
+            AxisRect::xz(x, z, max.y()),
+            AxisRect::xz(x, z, min.y()).flipped(),
+            AxisRect::yz(y, z, max.x()),
+            AxisRect::yz(y, z, min.x()).flipped(),
+        ];
+        Self { min, max, sides }
+    }
+}
+
+impl Shape for AxisBox {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)> {
+        self.sides.iter().fold(None, |closest, side| {
+            let closest_so_far = closest
+                .as_ref()
+                .map(|(distance, _)| *distance)
+                .unwrap_or(t_max);
+            side.intersect(ray, t_min, closest_so_far).or(closest)
+        })
+    }
+}
+
+impl BoundingBox for AxisBox {
+    fn bounding_box(&self, _exposure_time: f64) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+}
+
+/// A plane, e.g. to be used as floor. A bounding volume hierarchy can not deal with truly infinite
+/// shapes, so the plane ends at distance `extent` from `point`. Choose `extent` large enough for
+/// the edge to be out of sight, but not larger, since any ray passing close to the plane has to
+/// check for intersections with it.
+pub struct Plane {
+    point: Point,
+    /// Outward normal of unit length.
+    normal: Vec3,
+    extent: f64,
+    /// Together with the normal these form an orthonormal basis. Used for texture coordinates.
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+impl Plane {
+    /// Used if a scene does not specify an extent for the plane.
+    pub const DEFAULT_EXTENT: f64 = 10_000.;
+
+    pub fn new(point: Point, normal: Vec3, extent: f64) -> Self {
+        let normal = normal.unit();
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        Self {
+            point,
+            normal,
+            extent,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Shape for Plane {
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, Puncture)> {
+        let denominator = dot(ray.direction, self.normal);
+        if denominator.abs() < 1e-12 {
+            // Ray is parallel to the plane.
+            return None;
+        }
+        let t = dot(self.point - ray.origin, self.normal) / denominator;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let point = ray.at(t);
+        let offset = point - self.point;
+        if offset.length_squared() > self.extent * self.extent {
+            return None;
+        }
+        let mut puncture = Puncture::from_outward_normal(point, self.normal, &ray.direction);
+        // One texture repetition per unit of length.
+        puncture.texture_coordiantes = (dot(offset, self.tangent), dot(offset, self.bitangent));
+        Some((t, puncture))
+    }
+}
+
+impl BoundingBox for Plane {
+    fn bounding_box(&self, _exposure_time: f64) -> Aabb {
+        // Bounding box of a disc with radius `extent` around `point`. Padded, so it does not
+        // collapse along an axis, if the plane is perpendicular to it.
+        let padding = 1e-4;
+        let mut half_size = Vec3::ZERO;
+        for d in 0..3 {
+            half_size[d] =
+                self.extent * (1. - self.normal[d] * self.normal[d]).max(0.).sqrt() + padding;
+        }
+        Aabb::new(self.point - half_size, self.point + half_size)
+    }
+}
@@ -0,0 +1,222 @@
+//! Conversion between RGB colors and single wavelengths, used for spectral rendering. In spectral
+//! mode each path carries light of only one wavelength, so effects like dispersion can be
+//! rendered. The RGB colors of the scene are converted into spectra along the path, and the
+//! radiance arriving at the camera is converted back into RGB for each sample.
+
+use crate::vec3::Color;
+use std::sync::OnceLock;
+
+/// Range of wavelengths (in nanometers) [`sample_wavelength`] picks from.
+const MIN_WAVELENGTH: f64 = 360.;
+const MAX_WAVELENGTH: f64 = 830.;
+
+/// Wavelength in nanometers, chosen roughly proportional to the sensitivity of the human eye for
+/// `u` uniformly distributed in [0, 1). Wavelengths the eye barely perceives contribute little to
+/// the image, so sampling them often would only add noise. See Radziszewski et al. 2009,
+/// "An Improved Technique for Full Spectral Rendering".
+pub fn sample_wavelength(u: f64) -> f64 {
+    let wavelength = 538. - 138.888_889 * (0.856_910_62 - 1.827_501_97 * u).atanh();
+    // The fit slightly overshoots the range for `u` very close to one, where the density would be
+    // zero.
+    wavelength.clamp(MIN_WAVELENGTH, MAX_WAVELENGTH)
+}
+
+/// Probability density of [`sample_wavelength`] picking `wavelength`.
+pub fn wavelength_pdf(wavelength: f64) -> f64 {
+    if !(MIN_WAVELENGTH..=MAX_WAVELENGTH).contains(&wavelength) {
+        return 0.;
+    }
+    0.003_939_804_2 / (0.0072 * (wavelength - 538.)).cosh().powi(2)
+}
+
+/// Linear sRGB color a sensor records for light of `wavelength` and unit radiance. Scaled so that
+/// integrating it over all wavelengths yields white, i.e. a constant spectrum of one is recorded
+/// as `(1, 1, 1)`. Components are negative for wavelengths outside of the sRGB gamut.
+pub fn wavelength_to_rgb(wavelength: f64) -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white = WHITE.get_or_init(|| {
+        // Integrate with a step width of one nanometer.
+        let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+        (0..steps)
+            .map(|step| unbalanced_rgb(MIN_WAVELENGTH + step as f64 + 0.5))
+            .fold(Color::ZERO, |sum, rgb| sum + rgb)
+    });
+    let rgb = unbalanced_rgb(wavelength);
+    Color::new(rgb[0] / white[0], rgb[1] / white[1], rgb[2] / white[2])
+}
+
+fn unbalanced_rgb(wavelength: f64) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+    Color::new(
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    )
+}
+
+/// CIE 1931 standard observer color matching functions. Uses the multi-lobe Gaussian fit by Wyman
+/// et al. 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions".
+fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let lobe = |mean: f64, left: f64, right: f64| {
+        let deviation = if wavelength < mean { left } else { right };
+        let t = (wavelength - mean) / deviation;
+        (-0.5 * t * t).exp()
+    };
+    let x = 1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+        - 0.065 * lobe(501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/// Spectra of Smits' RGB to spectrum conversion, sampled in ten bins of equal width from 380 nm to
+/// 720 nm. See Smits 1999, "An RGB-to-Spectrum Conversion for Reflectances".
+const SMITS_FIRST_BIN: f64 = 380.;
+const SMITS_BIN_WIDTH: f64 = 34.;
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// Value at `wavelength` of a smooth spectrum, which looks like `color`. Colors brighter than one
+/// (e.g. of lights) are scaled down for the conversion and the result is scaled up again.
+pub fn rgb_to_spectral(color: &Color, wavelength: f64) -> f64 {
+    let scale = color.iter().cloned().fold(0., f64::max);
+    if scale <= 0. {
+        return 0.;
+    }
+    let (r, g, b) = (
+        color[0].max(0.) / scale,
+        color[1].max(0.) / scale,
+        color[2].max(0.) / scale,
+    );
+    // Linear interpolation between the centers of the two closest bins.
+    let position = ((wavelength - SMITS_FIRST_BIN) / SMITS_BIN_WIDTH - 0.5).clamp(0., 9.);
+    let bin = (position as usize).min(8);
+    let fraction = position - bin as f64;
+    let at = |spectrum: &[f64; 10]| spectrum[bin] * (1. - fraction) + spectrum[bin + 1] * fraction;
+
+    // Build the spectrum from white, plus the secondary and primary colors making up the rest.
+    let value = if r <= g && r <= b {
+        r * at(&SMITS_WHITE)
+            + if g <= b {
+                (g - r) * at(&SMITS_CYAN) + (b - g) * at(&SMITS_BLUE)
+            } else {
+                (b - r) * at(&SMITS_CYAN) + (g - b) * at(&SMITS_GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * at(&SMITS_WHITE)
+            + if r <= b {
+                (r - g) * at(&SMITS_MAGENTA) + (b - r) * at(&SMITS_BLUE)
+            } else {
+                (b - g) * at(&SMITS_MAGENTA) + (r - b) * at(&SMITS_RED)
+            }
+    } else {
+        b * at(&SMITS_WHITE)
+            + if r <= g {
+                (r - b) * at(&SMITS_YELLOW) + (g - r) * at(&SMITS_GREEN)
+            } else {
+                (g - b) * at(&SMITS_YELLOW) + (r - g) * at(&SMITS_RED)
+            }
+    };
+    value.max(0.) * scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Color recorded for the spectrum `radiance`, integrated with a step width of one nanometer.
+    fn record(radiance: impl Fn(f64) -> f64) -> Color {
+        let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+        (0..steps)
+            .map(|step| MIN_WAVELENGTH + step as f64 + 0.5)
+            .map(|wavelength| wavelength_to_rgb(wavelength) * radiance(wavelength))
+            .fold(Color::ZERO, |sum, rgb| sum + rgb)
+    }
+
+    fn assert_close(actual: &Color, expected: &Color, tolerance: f64) {
+        for channel in 0..3 {
+            assert!(
+                (actual[channel] - expected[channel]).abs() < tolerance,
+                "{} != {} in channel {}",
+                actual[channel],
+                expected[channel],
+                channel
+            );
+        }
+    }
+
+    #[test]
+    fn constant_spectrum_is_recorded_as_white() {
+        assert_close(&record(|_| 1.), &Color::ONE, 1e-9);
+    }
+
+    #[test]
+    fn rgb_to_spectral_round_trip() {
+        for color in &[
+            Color::ONE,
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(0.8, 0.2, 0.1),
+            Color::new(0.1, 0.7, 0.3),
+            Color::new(0.2, 0.4, 0.9),
+            // Lights are brighter than one.
+            Color::new(15., 15., 10.),
+        ] {
+            let recorded = record(|wavelength| rgb_to_spectral(color, wavelength));
+            let scale = color.iter().cloned().fold(0., f64::max);
+            // Smits' spectra only approximately reproduce saturated colors.
+            assert_close(&(recorded / scale), &(*color / scale), 0.05);
+        }
+        assert_close(
+            &record(|wavelength| rgb_to_spectral(&Color::ONE, wavelength)),
+            &Color::ONE,
+            0.01,
+        );
+        assert_eq!(rgb_to_spectral(&Color::ZERO, 500.), 0.);
+    }
+
+    #[test]
+    fn wavelength_pdf_is_normalized() {
+        let steps = 47_000;
+        let width = (MAX_WAVELENGTH - MIN_WAVELENGTH) / steps as f64;
+        let total: f64 = (0..steps)
+            .map(|step| wavelength_pdf(MIN_WAVELENGTH + (step as f64 + 0.5) * width) * width)
+            .sum();
+        assert!((total - 1.).abs() < 1e-3, "{}", total);
+    }
+
+    #[test]
+    fn sampled_wavelengths_follow_their_pdf() {
+        // The inverse of the cumulative distribution changes with the inverse of the density.
+        let h = 1e-6;
+        for i in 1..100 {
+            let u = i as f64 / 100.;
+            let wavelength = sample_wavelength(u);
+            assert!((MIN_WAVELENGTH..=MAX_WAVELENGTH).contains(&wavelength));
+            let derivative = (sample_wavelength(u + h) - sample_wavelength(u - h)) / (2. * h);
+            assert!((derivative * wavelength_pdf(wavelength) - 1.).abs() < 1e-3);
+        }
+        for &u in &[0., 1. - f64::EPSILON] {
+            let wavelength = sample_wavelength(u);
+            assert!(wavelength_pdf(wavelength) > 0., "{}", wavelength);
+        }
+    }
+}
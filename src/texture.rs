@@ -1,17 +1,40 @@
 use crate::{
     material::{Material, ScatterResult},
     shape::Puncture,
-    vec3::Vec3,
+    vec3::{Color, Vec3},
 };
-use rand::rngs::ThreadRng;
+use rand::RngCore;
+use std::sync::Arc;
 
 pub trait Texture {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         punctured: &Puncture,
         incoming: &Vec3,
+        _wavelength: Option<f64>,
     ) -> Option<ScatterResult>;
+
+    /// Light emitted at the punctured point of the surface.
+    fn emitted(&self, _punctured: &Puncture) -> Color {
+        Color::ZERO
+    }
+
+    /// See [`Material::transmittance`]. `punctured` is the point the ray leaves the object at.
+    fn transmittance(&self, _punctured: &Puncture, _length: f64) -> Color {
+        Color::ONE
+    }
+
+    /// See [`Material::evaluate`].
+    fn evaluate(
+        &self,
+        _punctured: &Puncture,
+        _incoming: &Vec3,
+        _direction: &Vec3,
+        _wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        None
+    }
 }
 
 impl<T> Texture for Box<T>
@@ -20,11 +43,66 @@ where
 {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
+        punctured: &Puncture,
+        incoming: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<ScatterResult> {
+        self.as_ref().scatter(rng, punctured, incoming, wavelength)
+    }
+
+    fn emitted(&self, punctured: &Puncture) -> Color {
+        self.as_ref().emitted(punctured)
+    }
+
+    fn transmittance(&self, punctured: &Puncture, length: f64) -> Color {
+        self.as_ref().transmittance(punctured, length)
+    }
+
+    fn evaluate(
+        &self,
+        punctured: &Puncture,
+        incoming: &Vec3,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        self.as_ref()
+            .evaluate(punctured, incoming, direction, wavelength)
+    }
+}
+
+/// Allows many shapes, e.g. all triangles of a mesh, to share the same texture.
+impl<T> Texture for Arc<T>
+where
+    T: Texture + ?Sized,
+{
+    fn scatter(
+        &self,
+        rng: &mut dyn RngCore,
         punctured: &Puncture,
         incoming: &Vec3,
+        wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
-        self.as_ref().scatter(rng, punctured, incoming)
+        self.as_ref().scatter(rng, punctured, incoming, wavelength)
+    }
+
+    fn emitted(&self, punctured: &Puncture) -> Color {
+        self.as_ref().emitted(punctured)
+    }
+
+    fn transmittance(&self, punctured: &Puncture, length: f64) -> Color {
+        self.as_ref().transmittance(punctured, length)
+    }
+
+    fn evaluate(
+        &self,
+        punctured: &Puncture,
+        incoming: &Vec3,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        self.as_ref()
+            .evaluate(punctured, incoming, direction, wavelength)
     }
 }
 
@@ -37,12 +115,42 @@ where
 {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         puncture: &Puncture,
         incoming: &Vec3,
+        wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
-        self.0
-            .scatter(rng, incoming, &puncture.normal, puncture.front_face)
+        self.0.scatter(
+            rng,
+            incoming,
+            &puncture.normal,
+            puncture.front_face,
+            wavelength,
+        )
+    }
+
+    fn emitted(&self, _puncture: &Puncture) -> Color {
+        self.0.emitted()
+    }
+
+    fn transmittance(&self, _puncture: &Puncture, length: f64) -> Color {
+        self.0.transmittance(length)
+    }
+
+    fn evaluate(
+        &self,
+        puncture: &Puncture,
+        incoming: &Vec3,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        self.0.evaluate(
+            incoming,
+            &puncture.normal,
+            puncture.front_face,
+            direction,
+            wavelength,
+        )
     }
 }
 
@@ -55,6 +163,15 @@ impl<E, O> Checkerd<E, O> {
     pub fn new(t0: E, t1: O) -> Self {
         Self { even: t0, odd: t1 }
     }
+
+    fn is_odd(&self, puncture: &Puncture) -> bool {
+        let point = &puncture.point;
+        let frequency = 10.;
+        let sines = (frequency * point.x()).sin()
+            * (frequency * point.y()).sin()
+            * (frequency * point.z()).sin();
+        sines < 0.
+    }
 }
 
 impl<E, O> Texture for Checkerd<E, O>
@@ -64,19 +181,46 @@ where
 {
     fn scatter(
         &self,
-        rng: &mut ThreadRng,
+        rng: &mut dyn RngCore,
         puncture: &Puncture,
         incoming: &Vec3,
+        wavelength: Option<f64>,
     ) -> Option<ScatterResult> {
-        let point = &puncture.point;
-        let frequency = 10.;
-        let sines = (frequency * point.x()).sin()
-            * (frequency * point.y()).sin()
-            * (frequency * point.z()).sin();
-        if sines < 0. {
-            self.odd.scatter(rng, puncture, incoming)
+        if self.is_odd(puncture) {
+            self.odd.scatter(rng, puncture, incoming, wavelength)
+        } else {
+            self.even.scatter(rng, puncture, incoming, wavelength)
+        }
+    }
+
+    fn emitted(&self, puncture: &Puncture) -> Color {
+        if self.is_odd(puncture) {
+            self.odd.emitted(puncture)
+        } else {
+            self.even.emitted(puncture)
+        }
+    }
+
+    fn transmittance(&self, puncture: &Puncture, length: f64) -> Color {
+        if self.is_odd(puncture) {
+            self.odd.transmittance(puncture, length)
+        } else {
+            self.even.transmittance(puncture, length)
+        }
+    }
+
+    fn evaluate(
+        &self,
+        puncture: &Puncture,
+        incoming: &Vec3,
+        direction: &Vec3,
+        wavelength: Option<f64>,
+    ) -> Option<(Color, f64)> {
+        if self.is_odd(puncture) {
+            self.odd.evaluate(puncture, incoming, direction, wavelength)
         } else {
-            self.even.scatter(rng, puncture, incoming)
+            self.even
+                .evaluate(puncture, incoming, direction, wavelength)
         }
     }
 }
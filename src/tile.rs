@@ -0,0 +1,102 @@
+use crate::{estimate::PixelEstimate, vec3::Color};
+use std::sync::Mutex;
+
+/// Rectangular block of pixels, rendered as one unit of work. Rows are counted from the top of the
+/// image.
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// Pixel coordinates `(column, row)` of this tile, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let Tile {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |row| (x..x + width).map(move |column| (column, row)))
+    }
+}
+
+/// Splits an image into tiles of at most `size` x `size` pixels. Tiles at the right and bottom
+/// border are smaller, if the image dimensions are not a multiple of `size`.
+pub fn tiles(image_width: u32, image_height: u32, size: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..image_height).step_by(size as usize) {
+        for x in (0..image_width).step_by(size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(image_width - x),
+                height: size.min(image_height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// Estimates of all pixels of the image, shared between all threads rendering tiles. Pixels are
+/// stored row by row, starting at the top left.
+pub struct Framebuffer {
+    width: u32,
+    pixels: Mutex<Vec<PixelEstimate>>,
+}
+
+impl Framebuffer {
+    /// No pixel has been sampled yet.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::from_estimates(width, vec![PixelEstimate::new(); (width * height) as usize])
+    }
+
+    /// Continues from estimates of a previous render.
+    pub fn from_estimates(width: u32, estimates: Vec<PixelEstimate>) -> Self {
+        Self {
+            width,
+            pixels: Mutex::new(estimates),
+        }
+    }
+
+    /// Current estimates of the pixels in `tile`, in the order of [`Tile::pixels`].
+    pub(crate) fn read_tile(&self, tile: &Tile) -> Vec<PixelEstimate> {
+        let pixels = self.pixels.lock().unwrap();
+        tile.pixels()
+            .map(|(column, row)| pixels[(row * self.width + column) as usize])
+            .collect()
+    }
+
+    /// Replaces the estimates of the pixels in `tile`. `estimates` are in the order of
+    /// [`Tile::pixels`].
+    pub(crate) fn write_tile(&self, tile: &Tile, estimates: &[PixelEstimate]) {
+        let mut pixels = self.pixels.lock().unwrap();
+        for ((column, row), estimate) in tile.pixels().zip(estimates) {
+            pixels[(row * self.width + column) as usize] = *estimate;
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Current color of each pixel, i.e. the mean of its samples. Pixels without samples are
+    /// black.
+    pub fn colors(&self) -> Vec<Color> {
+        let pixels = self.pixels.lock().unwrap();
+        pixels.iter().map(PixelEstimate::mean).collect()
+    }
+
+    /// Total number of samples taken by all pixels.
+    pub fn samples_rendered(&self) -> u64 {
+        let pixels = self.pixels.lock().unwrap();
+        pixels.iter().map(|estimate| estimate.count() as u64).sum()
+    }
+
+    pub fn into_estimates(self) -> Vec<PixelEstimate> {
+        self.pixels.into_inner().unwrap()
+    }
+}
@@ -0,0 +1,128 @@
+use crate::vec3::Color;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How linear radiance is turned into the colors of an 8 bit image.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Display {
+    /// Compresses radiance of any brightness into the displayable range.
+    #[serde(default)]
+    pub tone_mapper: ToneMapper,
+    /// Exposure correction in stops. Each stop doubles the brightness of the image before tone
+    /// mapping.
+    #[serde(default)]
+    pub exposure: f64,
+}
+
+impl Display {
+    /// `false` if the tone mapper is invalid or the exposure is not finite.
+    pub fn is_valid(&self) -> bool {
+        self.tone_mapper.is_valid() && self.exposure.is_finite()
+    }
+
+    /// Displayable color with channels in [0, 1], still linear (i.e. before the sRGB curve).
+    pub fn map(&self, color: &Color) -> Color {
+        let exposed = *color * 2f64.powf(self.exposure);
+        self.tone_mapper.map(&exposed)
+    }
+}
+
+/// Operators mapping linear radiance in [0, ∞) onto [0, 1]. Applied to each channel separately.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub enum ToneMapper {
+    /// Cuts off everything brighter than one. Bright lights become flat white.
+    #[default]
+    Clamp,
+    /// `x / (1 + x)`. Never reaches white.
+    Reinhard,
+    /// Reinhard operator, which maps `white` (and everything above) onto white.
+    ExtendedReinhard { white: f64 },
+    /// Filmic curve approximating the ACES reference rendering transform (Krzysztof Narkowicz).
+    Aces,
+    /// Filmic curve by John Hable, used in Uncharted 2.
+    Hable,
+}
+
+impl ToneMapper {
+    /// White point of the extended Reinhard operator, if chosen on the command line without one.
+    const DEFAULT_WHITE: f64 = 4.;
+
+    /// `false` if the white point of the extended Reinhard operator is not positive.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            ToneMapper::ExtendedReinhard { white } => white > 0.,
+            _ => true,
+        }
+    }
+
+    pub fn map(&self, color: &Color) -> Color {
+        let map_channel = |x: f64| {
+            let x = x.max(0.);
+            let mapped = match *self {
+                ToneMapper::Clamp => x,
+                ToneMapper::Reinhard => x / (1. + x),
+                ToneMapper::ExtendedReinhard { white } => x * (1. + x / (white * white)) / (1. + x),
+                ToneMapper::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+                ToneMapper::Hable => {
+                    // Exposure bias and linear white point proposed by Hable.
+                    const WHITE: f64 = 11.2;
+                    hable_partial(2. * x) / hable_partial(WHITE)
+                }
+            };
+            mapped.clamp(0., 1.)
+        };
+        Color::new(
+            map_channel(color[0]),
+            map_channel(color[1]),
+            map_channel(color[2]),
+        )
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    // Shoulder strength, linear strength, linear angle, toe strength, toe numerator and toe
+    // denominator.
+    let (a, b, c, d, e, f) = (0.15, 0.5, 0.1, 0.2, 0.02, 0.3);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+/// Parses the names used on the command line: `clamp`, `reinhard`, `extended-reinhard`, `aces` and
+/// `hable`. The white point of the extended Reinhard operator can be appended after a colon, e.g.
+/// `extended-reinhard:8`.
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, parameter) = match s.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (s, None),
+        };
+        match (name, parameter) {
+            ("clamp", None) => Ok(ToneMapper::Clamp),
+            ("reinhard", None) => Ok(ToneMapper::Reinhard),
+            ("extended-reinhard", None) => Ok(ToneMapper::ExtendedReinhard {
+                white: Self::DEFAULT_WHITE,
+            }),
+            ("extended-reinhard", Some(white)) => match white.parse() {
+                Ok(white) if white > 0. => Ok(ToneMapper::ExtendedReinhard { white }),
+                _ => Err(format!("Invalid white point '{}'.", white)),
+            },
+            ("aces", None) => Ok(ToneMapper::Aces),
+            ("hable", None) => Ok(ToneMapper::Hable),
+            _ => Err(format!(
+                "Unknown tone mapper '{}'. Expected one of: clamp, reinhard, extended-reinhard, \
+                aces, hable.",
+                s
+            )),
+        }
+    }
+}
+
+/// sRGB opto-electronic transfer function. Encodes a linear value in [0, 1] for display.
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{
+    bounding_box::{Aabb, BoundingBox},
+    hittable::{Hit, Hittable},
+    ray::Ray,
+    vec3::{dot, Point, Vec3},
+};
+use rand::RngCore;
+use std::ops::Mul;
+
+/// Affine transformation in homogeneous coordinates. Stored row major.
+#[derive(Clone, Copy)]
+pub struct Matrix4([[f64; 4]; 4]);
+
+impl Matrix4 {
+    pub const IDENTITY: Matrix4 = Matrix4([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    pub fn translation(offset: &Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        for i in 0..3 {
+            m.0[i][3] = offset[i];
+        }
+        m
+    }
+
+    pub fn scaling(factors: &Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        for i in 0..3 {
+            m.0[i][i] = factors[i];
+        }
+        m
+    }
+
+    /// Counter clockwise rotation around the x, y or z `axis` (`0`, `1` or `2`), looking from the
+    /// positive end of the axis towards the origin.
+    pub fn rotation(axis: usize, degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        // The two axes spanning the plane of rotation.
+        let a = (axis + 1) % 3;
+        let b = (axis + 2) % 3;
+        let mut m = Self::IDENTITY;
+        m.0[a][a] = cos;
+        m.0[a][b] = -sin;
+        m.0[b][a] = sin;
+        m.0[b][b] = cos;
+        m
+    }
+
+    pub fn transposed(&self) -> Self {
+        let mut m = Self::IDENTITY;
+        for i in 0..4 {
+            for j in 0..4 {
+                m.0[i][j] = self.0[j][i];
+            }
+        }
+        m
+    }
+
+    /// Inverse via Gauss-Jordan elimination. `None` if the matrix is singular, e.g. because an
+    /// axis has been scaled by zero.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut m = self.0;
+        let mut inv = Self::IDENTITY.0;
+        for column in 0..4 {
+            // Pivot with the largest absolute value for numerical stability.
+            let pivot = (column..4)
+                .max_by(|&l, &r| m[l][column].abs().partial_cmp(&m[r][column].abs()).unwrap())
+                .unwrap();
+            if m[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inv.swap(column, pivot);
+            let factor = m[column][column].recip();
+            for j in 0..4 {
+                m[column][j] *= factor;
+                inv[column][j] *= factor;
+            }
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = m[row][column];
+                for j in 0..4 {
+                    m[row][j] -= factor * m[column][j];
+                    inv[row][j] -= factor * inv[column][j];
+                }
+            }
+        }
+        Some(Matrix4(inv))
+    }
+
+    /// `true` if the matrix only rotates, translates and scales uniformly. Such transformations
+    /// keep all angles, and with them solid angles, intact.
+    pub fn preserves_angles(&self) -> bool {
+        let m = &self.0;
+        let columns = [0, 1, 2].map(|j| Vec3::new(m[0][j], m[1][j], m[2][j]));
+        let scale_squared = columns[0].length_squared();
+        let tolerance = 1e-9 * scale_squared;
+        scale_squared > 0.
+            && (0..3).all(|i| {
+                (0..3).all(|j| {
+                    let expected = if i == j { scale_squared } else { 0. };
+                    (dot(columns[i], columns[j]) - expected).abs() <= tolerance
+                })
+            })
+    }
+
+    pub fn transform_point(&self, point: &Point) -> Point {
+        let m = &self.0;
+        Point::new(
+            m[0][0] * point[0] + m[0][1] * point[1] + m[0][2] * point[2] + m[0][3],
+            m[1][0] * point[0] + m[1][1] * point[1] + m[1][2] * point[2] + m[1][3],
+            m[2][0] * point[0] + m[2][1] * point[1] + m[2][2] * point[2] + m[2][3],
+        )
+    }
+
+    /// Like `transform_point`, but ignores the translation.
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3::new(
+            m[0][0] * vector[0] + m[0][1] * vector[1] + m[0][2] * vector[2],
+            m[1][0] * vector[0] + m[1][1] * vector[1] + m[1][2] * vector[2],
+            m[2][0] * vector[0] + m[2][1] * vector[1] + m[2][2] * vector[2],
+        )
+    }
+}
+
+/// Matrix product. `(a * b)` applies `b` first and then `a`.
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Matrix4(m)
+    }
+}
+
+/// Places the inner hittable in the world using an affine transformation. Allows to place the same
+/// object at different positions, orientations and sizes.
+pub struct Transform<H> {
+    /// From object to world coordinates.
+    matrix: Matrix4,
+    /// From world to object coordinates.
+    inverse: Matrix4,
+    /// Normals must be transformed with the inverse transpose in order to stay perpendicular to
+    /// the surface under non uniform scaling.
+    normal_matrix: Matrix4,
+    inner: H,
+}
+
+impl<H> Transform<H> {
+    /// `None` if `matrix` is not invertible, e.g. because it scales an axis by zero.
+    pub fn new(matrix: Matrix4, inner: H) -> Option<Self> {
+        let inverse = matrix.inverse()?;
+        Some(Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.transposed(),
+            inner,
+        })
+    }
+}
+
+impl<H> Hittable for Transform<H>
+where
+    H: Hittable,
+{
+    fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        time: f64,
+        rng: &mut dyn RngCore,
+    ) -> Option<(f64, Hit<'_>)> {
+        // The direction is not normalized after the transformation, so the ray parameter `t` is
+        // the same in world and object coordinates.
+        let ray_in_object_coordinates = Ray::new(
+            self.inverse.transform_point(&ray.origin),
+            self.inverse.transform_vector(&ray.direction),
+        );
+        self.inner
+            .hit(&ray_in_object_coordinates, t_min, t_max, time, rng)
+            .map(|(distance, mut hit)| {
+                let intersection = &mut hit.intersection;
+                intersection.point = self.matrix.transform_point(&intersection.point);
+                // The normal keeps pointing against the ray, since the inverse transpose
+                // preserves the dot product with transformed directions.
+                intersection.normal = self
+                    .normal_matrix
+                    .transform_vector(&intersection.normal)
+                    .unit();
+                (distance, hit)
+            })
+    }
+}
+
+impl<B> BoundingBox for Transform<B>
+where
+    B: BoundingBox,
+{
+    fn bounding_box(&self, exposure_time: f64) -> Aabb {
+        // Transform all eight corners of the inner box and surround them.
+        let inner = self.inner.bounding_box(exposure_time);
+        let (min, max) = (inner.min(), inner.max());
+        let corners = (0..8).map(|index| {
+            Point::new(
+                if index & 1 == 0 { min.x() } else { max.x() },
+                if index & 2 == 0 { min.y() } else { max.y() },
+                if index & 4 == 0 { min.z() } else { max.z() },
+            )
+        });
+        corners
+            .map(|corner| {
+                let corner = self.matrix.transform_point(&corner);
+                Aabb::new(corner, corner)
+            })
+            .reduce(|a, b| Aabb::surrounding(&a, &b))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).length() < 1e-9);
+    }
+
+    fn placement() -> Matrix4 {
+        Matrix4::translation(&Vec3::new(1., -2., 3.))
+            * Matrix4::rotation(1, 30.)
+            * Matrix4::rotation(0, 45.)
+            * Matrix4::scaling(&Vec3::new(2., 0.5, 3.))
+    }
+
+    #[test]
+    fn inverse_undoes_the_transformation() {
+        let matrix = placement();
+        let inverse = matrix.inverse().unwrap();
+        let point = Point::new(0.3, -1.7, 2.2);
+        assert_close(
+            &inverse.transform_point(&matrix.transform_point(&point)),
+            &point,
+        );
+        assert_close(
+            &matrix.transform_vector(&inverse.transform_vector(&point)),
+            &point,
+        );
+    }
+
+    #[test]
+    fn scaling_by_zero_is_not_invertible() {
+        let matrix = Matrix4::scaling(&Vec3::new(1., 0., 1.));
+        assert!(matrix.inverse().is_none());
+    }
+
+    #[test]
+    fn rotation_is_counter_clockwise() {
+        let rotated = Matrix4::rotation(2, 90.).transform_vector(&Vec3::new(1., 0., 0.));
+        assert_close(&rotated, &Vec3::new(0., 1., 0.));
+    }
+
+    #[test]
+    fn only_similarities_preserve_angles() {
+        let rigid = Matrix4::translation(&Vec3::new(1., 2., 3.)) * Matrix4::rotation(1, 30.);
+        assert!(rigid.preserves_angles());
+        assert!((Matrix4::scaling(&Vec3::new(2., 2., 2.)) * rigid).preserves_angles());
+        assert!(!placement().preserves_angles());
+    }
+}
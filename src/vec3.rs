@@ -16,7 +16,7 @@ impl Vec3 {
         Self([x, y, z])
     }
 
-    pub fn random(rng: &mut impl Rng, min: f64, max: f64) -> Self {
+    pub fn random(rng: &mut (impl Rng + ?Sized), min: f64, max: f64) -> Self {
         Self([
             rng.gen_range(min, max),
             rng.gen_range(min, max),
@@ -61,6 +61,25 @@ pub fn cross(u: &Vec3, v: &Vec3) -> Vec3 {
     )
 }
 
+/// Two unit vectors, which together with `normal` (expected to be of unit length) form an
+/// orthonormal basis.
+pub fn orthonormal_basis(normal: &Vec3) -> (Vec3, Vec3) {
+    // Any vector not parallel to the normal works to construct the tangent.
+    let helper = if normal.x().abs() > 0.9 {
+        Vec3::new(0., 1., 0.)
+    } else {
+        Vec3::new(1., 0., 0.)
+    };
+    let bitangent = cross(normal, &helper).unit();
+    let tangent = cross(&bitangent, normal);
+    (tangent, bitangent)
+}
+
+/// Perceived brightness of a linear (Rec. 709) color.
+pub fn luminance(color: &Color) -> f64 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
 impl Deref for Vec3 {
     type Target = [f64; 3];
 